@@ -0,0 +1,12 @@
+//! Feeds the bzip2 decoder, behind a stream header for 900k blocks.
+
+#![no_main]
+
+use detect_filetype::{detect_layers, LayerLimits};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = b"BZh9".to_vec();
+    input.extend_from_slice(data);
+    detect_layers(&input, &LayerLimits::default());
+});
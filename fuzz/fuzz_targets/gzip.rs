@@ -0,0 +1,12 @@
+//! Feeds the DEFLATE decoder, behind a gzip header with no optional fields.
+
+#![no_main]
+
+use detect_filetype::{detect_layers, LayerLimits};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = b"\x1f\x8b\x08\0\0\0\0\0\0\xff".to_vec();
+    input.extend_from_slice(data);
+    detect_layers(&input, &LayerLimits::default());
+});
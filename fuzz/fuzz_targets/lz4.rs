@@ -0,0 +1,12 @@
+//! Feeds the LZ4 decoder, behind a frame header with no optional fields.
+
+#![no_main]
+
+use detect_filetype::{detect_layers, LayerLimits};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = b"\x04\x22\x4d\x18\x40\x40\xc0".to_vec();
+    input.extend_from_slice(data);
+    detect_layers(&input, &LayerLimits::default());
+});
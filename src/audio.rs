@@ -0,0 +1,192 @@
+//! Validation for audio formats, in particular raw MPEG audio and ADTS streams, which have
+//! no magic beyond a frame sync that plenty of other data contains.
+
+use crate::{FileType, Sample, Strength};
+
+/// Consecutive frames that must chain together before a raw stream is believed.
+const FRAMES_CHECKED: usize = 4;
+/// Longest MPEG audio frame: layer II at 384 kbit/s and 32 kHz, plus padding.
+const MPEG_MAX_FRAME_LEN: usize = 1729;
+/// How far into an input frames are followed.
+pub(crate) const FRAMES_HEAD_LEN: usize = FRAMES_CHECKED * MPEG_MAX_FRAME_LEN;
+
+const ID3_HEADER_LEN: usize = 10;
+const ID3_FOOTER_FLAG: u8 = 0x10;
+
+/// Bit rates in kbit/s, by bit rate index. Index 0 is "free format" and 15 is invalid.
+const MPEG1_LAYER1_RATES: [u32; 15] = [
+    0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+];
+const MPEG1_LAYER2_RATES: [u32; 15] = [
+    0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+];
+const MPEG1_LAYER3_RATES: [u32; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const MPEG2_LAYER1_RATES: [u32; 15] = [
+    0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+];
+const MPEG2_LAYER23_RATES: [u32; 15] =
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// A frame header's length, and the fields every frame of one stream shares.
+struct Frame {
+    len: usize,
+    stream: u32,
+}
+
+/// Parses an MPEG-1, 2 or 2.5 audio frame header, layers I to III.
+///
+/// Free format frames don't say how long they are, so they're rejected along with the
+/// reserved values.
+fn mpeg_frame(bytes: &[u8]) -> Option<Frame> {
+    let header = match bytes.get(..4)? {
+        &[0xff, b1, b2, b3] if b1 & 0xe0 == 0xe0 => [b1, b2, b3],
+        _ => return None,
+    };
+
+    let version = (header[0] >> 3) & 3;
+    let layer = (header[0] >> 1) & 3;
+    let rate_index = usize::from(header[1] >> 4);
+    let frequency_index = usize::from((header[1] >> 2) & 3);
+    let padding = u32::from((header[1] >> 1) & 1);
+
+    // Version 1 is reserved, and layer 0 is how ADTS headers set those bits.
+    if version == 1 || layer == 0 || rate_index == 0 || rate_index == 15 || frequency_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let rates = match (mpeg1, layer) {
+        (true, 3) => &MPEG1_LAYER1_RATES,
+        (true, 2) => &MPEG1_LAYER2_RATES,
+        (true, _) => &MPEG1_LAYER3_RATES,
+        (false, 3) => &MPEG2_LAYER1_RATES,
+        (false, _) => &MPEG2_LAYER23_RATES,
+    };
+    let bit_rate = rates[rate_index] * 1000;
+    let frequency = [44100, 48000, 32000][frequency_index]
+        >> match version {
+            3 => 0,
+            2 => 1,
+            _ => 2,
+        };
+
+    let len = match layer {
+        3 => (12 * bit_rate / frequency + padding) * 4,
+        1 if !mpeg1 => 72 * bit_rate / frequency + padding,
+        _ => 144 * bit_rate / frequency + padding,
+    };
+
+    Some(Frame {
+        len: len as usize,
+        stream: u32::from(header[0]) << 8 | u32::from(header[1] & 0x0c),
+    })
+}
+
+/// Parses an ADTS header, as raw AAC streams frame their data with.
+fn adts_frame(bytes: &[u8]) -> Option<Frame> {
+    let header = match bytes.get(..7)? {
+        &[0xff, b1, b2, b3, b4, b5, _] if b1 & 0xf6 == 0xf0 => [b1, b2, b3, b4, b5],
+        _ => return None,
+    };
+
+    let frequency_index = (header[1] >> 2) & 0xf;
+    let len = usize::from(header[2] & 3) << 11
+        | usize::from(header[3]) << 3
+        | usize::from(header[4] >> 5);
+    let header_len = if header[0] & 1 == 0 { 9 } else { 7 };
+
+    if frequency_index > 12 || len < header_len {
+        return None;
+    }
+
+    Some(Frame {
+        len,
+        stream: u32::from(header[0]) << 8 | u32::from(header[1] & 0xfc),
+    })
+}
+
+/// Follows frames from the start of the input, accepting it once `FRAMES_CHECKED` of them
+/// chain together or, for shorter inputs read to the end, once they fill it exactly.
+fn validate_frames(sample: &Sample<'_>, parse: fn(&[u8]) -> Option<Frame>) -> Option<Strength> {
+    let head = sample.head;
+    let stream = parse(head)?.stream;
+    let mut pos = 0;
+    let mut frames = 0;
+
+    while let Some(frame) = head.get(pos..).and_then(parse) {
+        if frame.stream != stream {
+            return None;
+        }
+
+        pos += frame.len;
+        frames += 1;
+        if frames == FRAMES_CHECKED {
+            return Some(Strength::Strong);
+        }
+    }
+
+    if frames > 1 && sample.tail.is_some() && pos as u64 == sample.len {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn validate_mpeg_audio(sample: &Sample<'_>) -> Option<Strength> {
+    validate_frames(sample, mpeg_frame)
+}
+
+pub(crate) fn validate_adts(sample: &Sample<'_>) -> Option<Strength> {
+    validate_frames(sample, adts_frame)
+}
+
+/// Length of the ID3v2 tag `bytes` starts with, header and footer included.
+fn id3_len(bytes: &[u8]) -> Option<usize> {
+    let header = bytes.get(..ID3_HEADER_LEN)?;
+    let (major, revision, flags, size) = (header[3], header[4], header[5], &header[6..]);
+
+    if &header[..3] != b"ID3" || major == 0xff || revision == 0xff {
+        return None;
+    }
+
+    // The size is "syncsafe": seven bits per byte, so it can't contain a frame sync.
+    if size.iter().any(|byte| byte & 0x80 != 0) {
+        return None;
+    }
+    let size = size
+        .iter()
+        .fold(0, |size, byte| size << 7 | usize::from(*byte));
+    let footer = if flags & ID3_FOOTER_FLAG != 0 {
+        ID3_HEADER_LEN
+    } else {
+        0
+    };
+
+    Some(ID3_HEADER_LEN + size + footer)
+}
+
+/// Checks the ID3v2 header has a defined version and a well-formed size.
+pub(crate) fn validate_id3(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < ID3_HEADER_LEN {
+        return Some(Strength::Weak);
+    }
+
+    id3_len(sample.head).map(|_| Strength::Strong)
+}
+
+/// The type of the audio after an ID3v2 tag, where the tag is short enough to see past.
+///
+/// Most tagged files are MP3, but FLAC and ADTS streams get tagged too.
+pub(crate) fn id3_subtype(sample: &Sample<'_>) -> Option<FileType> {
+    let audio = sample.head.get(id3_len(sample.head)?..)?;
+
+    if audio.starts_with(b"fLaC") {
+        Some(FileType::Flac)
+    } else if adts_frame(audio).is_some() {
+        Some(FileType::Aac)
+    } else {
+        None
+    }
+}
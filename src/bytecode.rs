@@ -0,0 +1,399 @@
+//! Bytecode and compiler IR: WebAssembly modules and components, Java class files, Android
+//! DEX files, LLVM bitcode and CPython's cached bytecode.
+
+use crate::{
+    bytes::{u16_be, u16_le, u32_le},
+    Sample, Strength,
+};
+
+pub(crate) const WASM_MAGIC: &[u8] = b"\0asm";
+/// Shared with universal Mach-O binaries, which are tried first.
+pub(crate) const CLASS_MAGIC: &[u8] = &[0xca, 0xfe, 0xba, 0xbe];
+pub(crate) const DEX_MAGIC: &[u8] = b"dex\n";
+pub(crate) const ODEX_MAGIC: &[u8] = b"dey\n";
+pub(crate) const BITCODE_MAGIC: &[u8] = b"BC\xc0\xde";
+pub(crate) const BITCODE_WRAPPER_MAGIC: &[u8] = &[0xde, 0xc0, 0x17, 0x0b];
+/// What follows the version-specific number in a `.pyc` magic.
+pub(crate) const PYC_MAGIC_SUFFIX: &[u8] = b"\r\n";
+
+/// The only version of the core module format, and the layer that marks a component.
+const WASM_MODULE_VERSION: u16 = 1;
+const WASM_COMPONENT_LAYER: u16 = 1;
+/// `major_version` of JDK 1.0.2 and 1.1, the first class files.
+const FIRST_CLASS_VERSION: u16 = 45;
+/// `major_version` of Java 5, the first release numbered without the "1.".
+const JAVA_5_CLASS_VERSION: u16 = 49;
+/// Highest `major_version` taken for a class file, leaving plenty of room for new releases.
+const MAX_CLASS_VERSION: u16 = 100;
+const DEX_ENDIAN_CONSTANT: u32 = 0x1234_5678;
+const DEX_REVERSE_ENDIAN_CONSTANT: u32 = 0x7856_3412;
+const DEX_HEADER_LEN: u32 = 0x70;
+/// Offset of `dex_offset` in an ODEX header, which points to the DEX file it wraps.
+const ODEX_DEX_OFFSET: usize = 8;
+const ODEX_HEADER_LEN: u32 = 40;
+/// Length of the bitcode wrapper header.
+const BITCODE_WRAPPER_LEN: u32 = 20;
+
+/// The magic numbers each CPython release, alphas and betas included, wrote `.pyc` files
+/// with, as given in `importlib/_bootstrap_external.py`.
+const PYC_MAGICS: &[(u16, u16, (u8, u8))] = &[
+    (3360, 3379, (3, 6)),
+    (3390, 3394, (3, 7)),
+    (3400, 3413, (3, 8)),
+    (3420, 3425, (3, 9)),
+    (3430, 3439, (3, 10)),
+    (3450, 3495, (3, 11)),
+    (3500, 3531, (3, 12)),
+    (3550, 3571, (3, 13)),
+];
+/// The first release with the PEP 552 flags word in its `.pyc` header.
+const PYC_FLAGS_VERSION: (u8, u8) = (3, 7);
+const PYC_HASH_BASED: u32 = 1 << 0;
+const PYC_CHECK_SOURCE: u32 = 1 << 1;
+
+/// Bytes a validator needs to follow an ODEX or bitcode wrapper header to what it wraps.
+pub(crate) const HEAD_LEN: usize = 64;
+
+/// The version a WebAssembly binary declares after its magic.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Wasm {
+    /// 1 for a core module. Components count their pre-release drafts up from `0xd`.
+    pub version: u16,
+    /// Whether this is a component-model component rather than a core module.
+    pub component: bool,
+}
+
+impl Wasm {
+    /// Parses the WebAssembly preamble `bytes` starts with, or returns `None` if it isn't a
+    /// WebAssembly binary.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(WASM_MAGIC) {
+            return None;
+        }
+
+        let version = u16_le(bytes, 4)?;
+        let component = match u16_le(bytes, 6)? {
+            0 => false,
+            WASM_COMPONENT_LAYER => true,
+            _ => return None,
+        };
+        if !component && version != WASM_MODULE_VERSION {
+            return None;
+        }
+
+        Some(Wasm { version, component })
+    }
+}
+
+/// The class file format version a Java class file was compiled to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct JavaClass {
+    pub major_version: u16,
+    /// 0 for most class files, or `0xffff` for ones using a release's preview features.
+    pub minor_version: u16,
+}
+
+impl JavaClass {
+    /// Parses the class file header `bytes` starts with, or returns `None` if it isn't a
+    /// class file.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(CLASS_MAGIC) {
+            return None;
+        }
+
+        let major_version = u16_be(bytes, 6)?;
+        if !(FIRST_CLASS_VERSION..=MAX_CLASS_VERSION).contains(&major_version) {
+            return None;
+        }
+
+        Some(JavaClass {
+            major_version,
+            minor_version: u16_be(bytes, 4)?,
+        })
+    }
+
+    /// The earliest Java release that can run the class, as its version is usually
+    /// written: `1.4` for major version 48, `8` for 52 and `21` for 65.
+    ///
+    /// JDK 1.0.2 and 1.1 share major version 45, which is reported as `1.1`.
+    pub fn release(&self) -> String {
+        if self.major_version >= JAVA_5_CLASS_VERSION {
+            (self.major_version - (JAVA_5_CLASS_VERSION - 5)).to_string()
+        } else {
+            format!("1.{}", self.major_version.max(FIRST_CLASS_VERSION) - 44)
+        }
+    }
+}
+
+/// The format version of an Android DEX file, and whether it is an optimised (ODEX) one.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Dex {
+    /// The three-digit version after the magic, such as 35 or 39.
+    pub version: u16,
+    /// Whether this is an ODEX file, made by `dexopt` to wrap a DEX file.
+    pub optimized: bool,
+}
+
+impl Dex {
+    /// Parses the DEX or ODEX magic `bytes` starts with, or returns `None` if it isn't one.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let optimized = match bytes.get(..4)? {
+            DEX_MAGIC => false,
+            ODEX_MAGIC => true,
+            _ => return None,
+        };
+
+        match *bytes.get(4..8)? {
+            [hundreds, tens, units, 0]
+                if [hundreds, tens, units].iter().all(u8::is_ascii_digit) =>
+            {
+                let digit = |byte: u8| u16::from(byte - b'0');
+                Some(Dex {
+                    version: digit(hundreds) * 100 + digit(tens) * 10 + digit(units),
+                    optimized,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The CPython release that wrote a `.pyc` file, and how it is checked against its source.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Pyc {
+    /// The number at the start of the magic, which changes whenever the bytecode does.
+    pub magic: u16,
+    /// The CPython release the magic belongs to, as `(major, minor)`.
+    pub version: (u8, u8),
+    /// The PEP 552 flags word. Python 3.6 has no such word, so this is 0, as it is for
+    /// timestamp-based files from later releases.
+    pub flags: u32,
+}
+
+impl Pyc {
+    /// Parses the `.pyc` header `bytes` starts with, or returns `None` if it isn't one from
+    /// CPython 3.6 to 3.13.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(2..4)? != PYC_MAGIC_SUFFIX {
+            return None;
+        }
+
+        let magic = u16_le(bytes, 0)?;
+        let version = pyc_version(magic)?;
+
+        let flags = if version >= PYC_FLAGS_VERSION {
+            u32_le(bytes, 4)?
+        } else {
+            0
+        };
+        // Only the two defined bits, and checking the source only makes sense for a hash.
+        if flags & !(PYC_HASH_BASED | PYC_CHECK_SOURCE) != 0 || flags == PYC_CHECK_SOURCE {
+            return None;
+        }
+
+        Some(Pyc {
+            magic,
+            version,
+            flags,
+        })
+    }
+
+    /// Whether the file holds a hash of its source rather than the source's timestamp.
+    pub fn hash_based(&self) -> bool {
+        self.flags & PYC_HASH_BASED != 0
+    }
+
+    /// Whether a hash-based file is checked against its source before it is used, rather
+    /// than trusted as is.
+    pub fn check_source(&self) -> bool {
+        self.flags & PYC_CHECK_SOURCE != 0
+    }
+}
+
+/// Accepts a module of the one core version, or a component.
+pub(crate) fn validate_wasm(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < 8 {
+        return Some(Strength::Weak);
+    }
+
+    Wasm::parse(sample.head).map(|_| Strength::Strong)
+}
+
+/// Checks the major version is one a Java release has used, or plausibly will.
+pub(crate) fn validate_class(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < 8 {
+        return Some(Strength::Weak);
+    }
+
+    JavaClass::parse(sample.head).map(|_| Strength::Strong)
+}
+
+/// Checks the version, then the header size and byte order tag once they're read.
+pub(crate) fn validate_dex(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < 8 {
+        return Some(Strength::Weak);
+    }
+    Dex::parse(sample.head)?;
+
+    match (u32_le(sample.head, 36), u32_le(sample.head, 40)) {
+        (Some(DEX_HEADER_LEN), Some(DEX_ENDIAN_CONSTANT | DEX_REVERSE_ENDIAN_CONSTANT)) => {
+            Some(Strength::Strong)
+        }
+        (Some(_), Some(_)) => None,
+        _ => Some(Strength::Weak),
+    }
+}
+
+/// Checks the version and follows `dex_offset` to the wrapped DEX file's magic.
+pub(crate) fn validate_odex(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < 8 {
+        return Some(Strength::Weak);
+    }
+    Dex::parse(sample.head)?;
+
+    wrapped(sample, ODEX_DEX_OFFSET, ODEX_HEADER_LEN, DEX_MAGIC)
+}
+
+/// The CPython release a `.pyc` magic number belongs to.
+fn pyc_version(magic: u16) -> Option<(u8, u8)> {
+    PYC_MAGICS
+        .iter()
+        .find(|(first, last, _)| (*first..=*last).contains(&magic))
+        .map(|&(_, _, version)| version)
+}
+
+/// Checks the magic belongs to a known CPython release and, from 3.7, that the flags word
+/// only has the bits PEP 552 defines. Only the `\r\n` is common to every release, so an
+/// unknown number is rejected however short the input.
+pub(crate) fn validate_pyc(sample: &Sample<'_>) -> Option<Strength> {
+    let version = pyc_version(u16_le(sample.head, 0)?)?;
+    if version >= PYC_FLAGS_VERSION && sample.head.len() < 8 {
+        return Some(Strength::Weak);
+    }
+
+    Pyc::parse(sample.head).map(|_| Strength::Strong)
+}
+
+/// Follows the wrapper header's offset to the raw bitcode magic.
+pub(crate) fn validate_bitcode_wrapper(sample: &Sample<'_>) -> Option<Strength> {
+    wrapped(sample, 8, BITCODE_WRAPPER_LEN, BITCODE_MAGIC)
+}
+
+/// Follows the offset at `offset_at` in a wrapper header of `header_len` bytes, expecting
+/// `magic` where it points.
+fn wrapped(
+    sample: &Sample<'_>,
+    offset_at: usize,
+    header_len: u32,
+    magic: &[u8],
+) -> Option<Strength> {
+    let offset = match u32_le(sample.head, offset_at) {
+        Some(offset) if offset < header_len => return None,
+        Some(offset) => offset as usize,
+        None => return Some(Strength::Weak),
+    };
+
+    match sample.head.get(offset..) {
+        Some(wrapped) if wrapped.starts_with(magic) => Some(Strength::Strong),
+        Some(wrapped) if wrapped.len() >= magic.len() => None,
+        _ => Some(Strength::Weak),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dex, JavaClass, Pyc, Wasm};
+    use std::{fs, io};
+
+    #[test]
+    fn parse() -> io::Result<()> {
+        assert_eq!(
+            Wasm::parse(&fs::read("test.wasm")?),
+            Some(Wasm {
+                version: 1,
+                component: false,
+            })
+        );
+        assert_eq!(
+            Wasm::parse(&fs::read("test-component.wasm")?),
+            Some(Wasm {
+                version: 0xd,
+                component: true,
+            })
+        );
+
+        let class = JavaClass::parse(&fs::read("test.class")?);
+        assert_eq!(
+            class,
+            Some(JavaClass {
+                major_version: 52,
+                minor_version: 0,
+            })
+        );
+        assert_eq!(class.map(|class| class.release()), Some("8".to_string()));
+
+        assert_eq!(
+            Dex::parse(&fs::read("test.dex")?),
+            Some(Dex {
+                version: 35,
+                optimized: false,
+            })
+        );
+        assert_eq!(
+            Dex::parse(&fs::read("test.odex")?),
+            Some(Dex {
+                version: 36,
+                optimized: true,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn java_releases() {
+        let release = |major_version| {
+            JavaClass {
+                major_version,
+                minor_version: 0,
+            }
+            .release()
+        };
+
+        assert_eq!(release(45), "1.1");
+        assert_eq!(release(48), "1.4");
+        assert_eq!(release(49), "5");
+        assert_eq!(release(65), "21");
+    }
+
+    #[test]
+    fn pyc() -> io::Result<()> {
+        let timestamp = Pyc::parse(&fs::read("test.pyc")?);
+        assert_eq!(
+            timestamp,
+            Some(Pyc {
+                magic: 3495,
+                version: (3, 11),
+                flags: 0,
+            })
+        );
+        assert_eq!(timestamp.map(|pyc| pyc.hash_based()), Some(false));
+
+        let hash = Pyc::parse(&fs::read("test-hash.pyc")?);
+        assert_eq!(hash.map(|pyc| pyc.version), Some((3, 11)));
+        assert_eq!(hash.map(|pyc| pyc.hash_based()), Some(true));
+        assert_eq!(hash.map(|pyc| pyc.check_source()), Some(true));
+
+        // Python 3.6 has a modification time where later releases have flags.
+        let legacy = Pyc::parse(b"\x33\x0d\x0d\x0a\x7a\x97\xd0\x6a\x0c\0\0\0");
+        assert_eq!(
+            legacy.map(|pyc| (pyc.version, pyc.flags)),
+            Some(((3, 6), 0))
+        );
+
+        // Python 2.7's magic.
+        assert_eq!(Pyc::parse(b"\x03\xf3\x0d\x0a\0\0\0\0"), None);
+
+        Ok(())
+    }
+}
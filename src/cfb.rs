@@ -0,0 +1,149 @@
+//! Compound File Binary, the "OLE2" container legacy Office documents and Outlook messages
+//! are stored in, told apart by the names of the streams in its directory.
+
+use crate::{
+    bytes::{u16_le, u32_le},
+    FileType, Sample, Strength,
+};
+
+pub(crate) const MAGIC: &[u8] = &[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+
+const HEADER_LEN: usize = 512;
+const BYTE_ORDER: u16 = 0xfffe;
+/// Sector sizes of major versions 3 and 4, as powers of two.
+const SECTOR_SHIFTS: &[u32] = &[9, 12];
+/// FAT sector locations held in the header itself, before any DIFAT sectors.
+const HEADER_DIFAT_LEN: usize = 109;
+const HEADER_DIFAT_OFFSET: usize = 0x4c;
+/// Sector numbers from this up are markers such as end-of-chain rather than locations.
+const MAX_SECTOR: u32 = 0xffff_fffa;
+const DIR_ENTRY_LEN: usize = 128;
+const DIR_NAME_LEN: usize = 64;
+const STORAGE: u8 = 1;
+const STREAM: u8 = 2;
+/// Directory sectors followed before giving up on a chain, which may loop.
+const MAX_DIR_SECTORS: usize = 64;
+
+/// How far into an input the directory is looked for: the header and, with 512-byte
+/// sectors, the seven after it. A directory further in leaves the file a plain compound file.
+pub(crate) const HEAD_LEN: usize = 4096;
+
+/// Checks the header's byte order mark and sector size.
+pub(crate) fn validate(sample: &Sample<'_>) -> Option<Strength> {
+    match (u16_le(sample.head, 0x1c), u16_le(sample.head, 0x1e)) {
+        (Some(BYTE_ORDER), Some(shift)) if SECTOR_SHIFTS.contains(&u32::from(shift)) => {
+            Some(Strength::Strong)
+        }
+        (Some(_), Some(_)) => None,
+        _ => Some(Strength::Weak),
+    }
+}
+
+struct Header<'a> {
+    bytes: &'a [u8],
+    sector_shift: u32,
+    first_dir_sector: u32,
+}
+
+impl<'a> Header<'a> {
+    fn parse(bytes: &'a [u8]) -> Option<Self> {
+        Some(Header {
+            bytes,
+            sector_shift: u32::from(u16_le(bytes, 0x1e)?),
+            first_dir_sector: u32_le(bytes, 0x30)?,
+        })
+    }
+
+    fn sector(&self, sector: u32) -> Option<&'a [u8]> {
+        if sector >= MAX_SECTOR {
+            return None;
+        }
+
+        // The header takes up the first sector's worth of bytes, so sector 0 follows it.
+        let len = 1usize << self.sector_shift;
+        let start = (sector as usize + 1).checked_mul(len)?;
+        self.bytes.get(start..start.checked_add(len)?)
+    }
+
+    /// Looks up the sector after `sector` in its chain, using the FAT sectors the header
+    /// lists.
+    fn next(&self, sector: u32) -> Option<u32> {
+        let per_sector = 1 << (self.sector_shift - 2);
+        let fat_index = sector as usize / per_sector;
+        if fat_index >= HEADER_DIFAT_LEN {
+            return None;
+        }
+
+        let fat_sector = u32_le(self.bytes, HEADER_DIFAT_OFFSET + fat_index * 4)?;
+        u32_le(self.sector(fat_sector)?, (sector as usize % per_sector) * 4)
+    }
+}
+
+/// The names of the storages and streams in the directory, as far as it can be read.
+fn names(bytes: &[u8]) -> Vec<String> {
+    let header = match Header::parse(bytes) {
+        Some(header) if SECTOR_SHIFTS.contains(&header.sector_shift) => header,
+        _ => return Vec::new(),
+    };
+
+    let mut names = Vec::new();
+    let mut sector = header.first_dir_sector;
+
+    for _ in 0..MAX_DIR_SECTORS {
+        let entries = match header.sector(sector) {
+            Some(entries) => entries,
+            None => break,
+        };
+
+        for entry in entries.chunks_exact(DIR_ENTRY_LEN) {
+            let name_len = usize::from(u16_le(entry, DIR_NAME_LEN).unwrap_or(0));
+            let used = matches!(entry[DIR_NAME_LEN + 2], STORAGE | STREAM);
+            if !used || !(2..=DIR_NAME_LEN).contains(&name_len) {
+                continue;
+            }
+
+            // The length counts the UTF-16 terminator.
+            let units = entry[..name_len - 2]
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+            names.push(
+                std::char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER))
+                    .collect(),
+            );
+        }
+
+        sector = match header.next(sector) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    names
+}
+
+/// Works out which application wrote the compound file from its stream names.
+pub(crate) fn subtype(sample: &Sample<'_>) -> Option<FileType> {
+    // Buffers are cut to the same window as streams, so they aren't subtyped differently.
+    let head = &sample.head[..sample.head.len().min(HEAD_LEN)];
+    if head.len() < HEADER_LEN {
+        return None;
+    }
+
+    let names = names(head);
+    let has = |wanted: &str| names.iter().any(|name| name == wanted);
+
+    if has("WordDocument") {
+        Some(FileType::Doc)
+    } else if has("Workbook") || has("Book") {
+        Some(FileType::Xls)
+    } else if has("PowerPoint Document") {
+        Some(FileType::Ppt)
+    } else if has("__properties_version1.0")
+        || names.iter().any(|name| name.starts_with("__substg1.0_"))
+    {
+        Some(FileType::Msg)
+    } else {
+        None
+    }
+}
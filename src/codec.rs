@@ -0,0 +1,227 @@
+//! Identifying the codecs inside audio and video containers, for the formats whose
+//! container says what it holds up front.
+
+use crate::{detect_filetype, isobmff, FileType};
+
+const OGG_PAGE_HEADER_LEN: usize = 27;
+/// Header type flag marking the first page of a logical stream.
+const OGG_BOS: u8 = 0x02;
+
+/// A codec audio or video is coded with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Codec {
+    // -- Audio --
+    Vorbis,
+    Opus,
+    Flac,
+    Speex,
+    /// AAC, or another MPEG-4 audio object type.
+    Aac,
+    /// MPEG audio, layers I to III.
+    Mp3,
+
+    // -- Video --
+    Theora,
+    /// H.264.
+    Avc,
+    /// H.265.
+    Hevc,
+    Av1,
+    Vp9,
+}
+
+impl Codec {
+    /// Whether this is a video codec rather than an audio one.
+    pub fn is_video(&self) -> bool {
+        matches!(
+            self,
+            Codec::Theora | Codec::Avc | Codec::Hevc | Codec::Av1 | Codec::Vp9
+        )
+    }
+}
+
+/// A track or logical stream inside a container, as returned by [`detect_codecs`].
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Track {
+    pub codec: Codec,
+    /// The track's entry in an RFC 6381 `codecs` parameter, such as `avc1.64001f` or
+    /// `mp4a.40.2`.
+    ///
+    /// This carries the profile and level where the container records them, and is just
+    /// the codec's name where it doesn't.
+    pub codecs: String,
+}
+
+/// Iterates over the first packet of each logical stream, from the pages that begin the
+/// streams at the start of an Ogg file.
+fn ogg_first_packets(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        let page = bytes.get(pos..)?;
+        if !page.starts_with(b"OggS") || page.get(5)? & OGG_BOS == 0 {
+            return None;
+        }
+
+        let segments = usize::from(*page.get(OGG_PAGE_HEADER_LEN - 1)?);
+        let lacing = page.get(OGG_PAGE_HEADER_LEN..OGG_PAGE_HEADER_LEN + segments)?;
+        let body = OGG_PAGE_HEADER_LEN + segments;
+        let body_len: usize = lacing.iter().map(|&len| usize::from(len)).sum();
+        // A packet ends at the first lacing value below 255.
+        let packet_segments = lacing
+            .iter()
+            .position(|&len| len < 255)
+            .map_or(segments, |last| last + 1);
+        let packet_len: usize = lacing[..packet_segments]
+            .iter()
+            .map(|&len| usize::from(len))
+            .sum();
+
+        pos += body + body_len;
+        page.get(body..(body + packet_len).min(page.len()))
+    })
+}
+
+fn ogg_track(packet: &[u8]) -> Option<Track> {
+    let (codec, codecs) = [
+        (&b"\x01vorbis"[..], Codec::Vorbis, "vorbis"),
+        (b"OpusHead", Codec::Opus, "opus"),
+        (b"\x7fFLAC", Codec::Flac, "flac"),
+        (b"Speex   ", Codec::Speex, "speex"),
+        (b"\x80theora", Codec::Theora, "theora"),
+    ]
+    .iter()
+    .find(|(magic, _, _)| packet.starts_with(magic))
+    .map(|(_, codec, codecs)| (*codec, *codecs))?;
+
+    Some(Track {
+        codec,
+        codecs: codecs.to_string(),
+    })
+}
+
+fn tracks(bytes: &[u8], file_type: FileType) -> Vec<Track> {
+    match file_type {
+        FileType::Ogg => ogg_first_packets(bytes).filter_map(ogg_track).collect(),
+        FileType::Mp4
+        | FileType::M4a
+        | FileType::M4v
+        | FileType::Mov
+        | FileType::ThreeGp
+        | FileType::ThreeG2 => isobmff::tracks(bytes),
+        _ => Vec::new(),
+    }
+}
+
+/// Finds the codec of each track in an Ogg or ISO base media (MP4, QuickTime, 3GPP) file.
+///
+/// Ogg streams are identified from the pages that begin them, and MP4 tracks from the
+/// sample entries in the `moov` box, so a file whose `moov` comes after its media data
+/// must be passed in whole. Tracks in codecs this doesn't know are left out, and any other
+/// type of input gives no tracks at all.
+pub fn detect_codecs(bytes: &[u8]) -> Vec<Track> {
+    detect_filetype(bytes).map_or_else(Vec::new, |ty| tracks(bytes, ty))
+}
+
+/// The media type of `bytes`, with an RFC 6381 `codecs` parameter listing its tracks'
+/// codecs if [`detect_codecs`] finds any.
+///
+/// An Ogg file holding video is given `video/ogg` rather than [`FileType::mime_type`]'s
+/// `audio/ogg`.
+pub fn mime_with_codecs(bytes: &[u8]) -> Option<String> {
+    let file_type = detect_filetype(bytes)?;
+    let tracks = tracks(bytes, file_type);
+
+    let mime = match file_type {
+        FileType::Ogg if tracks.iter().any(|track| track.codec.is_video()) => "video/ogg",
+        _ => file_type.mime_type(),
+    };
+
+    if tracks.is_empty() {
+        return Some(mime.to_string());
+    }
+
+    let codecs: Vec<&str> = tracks.iter().map(|track| track.codecs.as_str()).collect();
+    Some(format!("{}; codecs=\"{}\"", mime, codecs.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_codecs, mime_with_codecs, Codec, Track};
+    use std::{fs, io};
+
+    fn track(codec: Codec, codecs: &str) -> Track {
+        Track {
+            codec,
+            codecs: codecs.to_string(),
+        }
+    }
+
+    /// A beginning-of-stream Ogg page holding a single packet, without a valid CRC.
+    fn bos_page(serial: u8, packet: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0\x02".to_vec();
+        page.extend(&[0; 8]);
+        page.extend(&[serial, 0, 0, 0]);
+        page.extend(&[0; 8]);
+        page.extend(&[1, packet.len() as u8]);
+        page.extend(packet);
+        page
+    }
+
+    #[test]
+    fn ogg() -> io::Result<()> {
+        let vorbis = fs::read("test.ogg")?;
+        assert_eq!(detect_codecs(&vorbis), vec![track(Codec::Vorbis, "vorbis")]);
+        assert_eq!(
+            mime_with_codecs(&vorbis).as_deref(),
+            Some("audio/ogg; codecs=\"vorbis\"")
+        );
+
+        let mut video = bos_page(1, b"\x80theora\x03\x02\x01");
+        video.extend(bos_page(2, b"OpusHead\x01\x02"));
+        assert_eq!(
+            detect_codecs(&video),
+            vec![track(Codec::Theora, "theora"), track(Codec::Opus, "opus")]
+        );
+        assert_eq!(
+            mime_with_codecs(&video).as_deref(),
+            Some("video/ogg; codecs=\"theora, opus\"")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn mp4() -> io::Result<()> {
+        let mp4 = fs::read("test.mp4")?;
+        assert_eq!(
+            detect_codecs(&mp4),
+            vec![
+                track(Codec::Avc, "avc1.64001f"),
+                track(Codec::Aac, "mp4a.40.2")
+            ]
+        );
+        assert_eq!(
+            mime_with_codecs(&mp4).as_deref(),
+            Some("video/mp4; codecs=\"avc1.64001f, mp4a.40.2\"")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn no_tracks() -> io::Result<()> {
+        let png = fs::read("test.png")?;
+        assert_eq!(detect_codecs(&png), vec![]);
+        assert_eq!(mime_with_codecs(&png).as_deref(), Some("image/png"));
+
+        // The movie box comes after the media data, and is cut off here.
+        let m4a = fs::read("test.m4a")?;
+        assert_eq!(detect_codecs(&m4a), vec![]);
+        assert_eq!(mime_with_codecs(&m4a).as_deref(), Some("audio/mp4"));
+
+        assert_eq!(mime_with_codecs(&[]), None);
+
+        Ok(())
+    }
+}
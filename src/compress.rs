@@ -0,0 +1,64 @@
+//! Validation for compressed stream formats.
+
+use crate::{bytes::u32_le, Sample, Strength};
+
+pub(crate) const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+pub(crate) const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4d, 0x18];
+
+/// Zstandard and LZ4 share skippable frames, whose magic numbers run from this to
+/// `0x184D2A5F`.
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a50;
+
+/// How far into a stream skippable frames are followed.
+pub(crate) const HEAD_LEN: usize = 1024;
+
+/// Gzip header flags that no version of the format defines.
+const GZIP_RESERVED_FLAGS: u8 = 0xe0;
+/// Highest operating system code RFC 1952 assigns, apart from 255 for "unknown".
+const GZIP_MAX_OS: u8 = 13;
+
+/// Checks the gzip header's flags and OS fields hold values RFC 1952 allows.
+pub(crate) fn validate_gzip(sample: &Sample<'_>) -> Option<Strength> {
+    let (flags, os) = match (sample.head.get(3), sample.head.get(9)) {
+        (Some(&flags), Some(&os)) => (flags, os),
+        _ => return Some(Strength::Weak),
+    };
+
+    if flags & GZIP_RESERVED_FLAGS == 0 && (os <= GZIP_MAX_OS || os == 255) {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+/// Skips any skippable frames at the start of `head`, returning what follows them.
+pub(crate) fn skip_frames(head: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+
+    loop {
+        if u32_le(head, pos)? & !0xf != SKIPPABLE_MAGIC {
+            return head.get(pos..);
+        }
+
+        let size = u32_le(head, pos + 4)? as usize;
+        pos = pos.checked_add(8)?.checked_add(size)?;
+    }
+}
+
+fn skippable_then(sample: &Sample<'_>, magic: &[u8]) -> Option<Strength> {
+    if skip_frames(sample.head)?.starts_with(magic) {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+/// Accepts skippable frames that lead up to a Zstandard frame.
+pub(crate) fn validate_skippable_zstd(sample: &Sample<'_>) -> Option<Strength> {
+    skippable_then(sample, ZSTD_MAGIC)
+}
+
+/// Accepts skippable frames that lead up to an LZ4 frame.
+pub(crate) fn validate_skippable_lz4(sample: &Sample<'_>) -> Option<Strength> {
+    skippable_then(sample, LZ4_MAGIC)
+}
@@ -0,0 +1,75 @@
+//! Validation for document formats: PDF, whose header readers look for some way into the
+//! file, and PostScript, whose EPS variants need more than the first few bytes to tell
+//! apart.
+
+use crate::{bytes::u32_le, Sample, Strength};
+
+const PDF_MAGIC: &[u8] = b"%PDF-";
+/// How far into a file readers look for the PDF header.
+pub(crate) const PDF_HEAD_LEN: usize = 1024;
+
+/// Length of the header in front of the PostScript in a DOS EPS binary.
+const DOS_EPS_HEADER_LEN: u32 = 30;
+/// How far into a DOS EPS binary its PostScript section is looked for.
+pub(crate) const DOS_EPS_HEAD_LEN: usize = 64;
+/// Longest first line of an EPS file, `%!PS-Adobe-3.0 EPSF-3.0` and any trailing spaces.
+pub(crate) const EPS_LINE_LEN: usize = 64;
+
+/// Whether `bytes` has a version number, as in `1.7`, straight after the PDF magic.
+fn pdf_version_at(bytes: &[u8], pos: usize) -> bool {
+    match bytes.get(pos + PDF_MAGIC.len()..pos + PDF_MAGIC.len() + 3) {
+        Some(&[major, b'.', minor]) => major.is_ascii_digit() && minor.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+/// Checks `%PDF-` is followed by a `digit.digit` version.
+pub(crate) fn validate_pdf(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < PDF_MAGIC.len() + 3 {
+        Some(Strength::Weak)
+    } else if pdf_version_at(sample.head, 0) {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+/// Finds a PDF header preceded by junk, such as a mail header or a stray byte order mark.
+pub(crate) fn validate_pdf_offset(sample: &Sample<'_>) -> Option<Strength> {
+    let head = &sample.head[..sample.head.len().min(PDF_HEAD_LEN)];
+
+    head.windows(PDF_MAGIC.len())
+        .enumerate()
+        .any(|(pos, window)| window == PDF_MAGIC && pdf_version_at(head, pos))
+        .then_some(Strength::Strong)
+}
+
+/// Accepts PostScript whose first line declares it encapsulated, as in
+/// `%!PS-Adobe-3.0 EPSF-3.0`.
+pub(crate) fn validate_eps(sample: &Sample<'_>) -> Option<Strength> {
+    let line = sample.head[..sample.head.len().min(EPS_LINE_LEN)]
+        .split(|&byte| byte == b'\r' || byte == b'\n')
+        .next()?;
+
+    line.windows(6)
+        .any(|window| window == b" EPSF-")
+        .then_some(Strength::Strong)
+}
+
+/// Checks the DOS EPS binary header points past itself to the PostScript section.
+pub(crate) fn validate_dos_eps(sample: &Sample<'_>) -> Option<Strength> {
+    let (offset, len) = match (u32_le(sample.head, 4), u32_le(sample.head, 8)) {
+        (Some(offset), Some(len)) => (offset, len),
+        _ => return Some(Strength::Weak),
+    };
+
+    if offset < DOS_EPS_HEADER_LEN || len == 0 {
+        return None;
+    }
+
+    match sample.head.get(offset as usize..) {
+        Some(postscript) if postscript.starts_with(b"%!PS") => Some(Strength::Strong),
+        Some(postscript) if postscript.len() >= 4 => None,
+        _ => Some(Strength::Weak),
+    }
+}
@@ -0,0 +1,239 @@
+//! ELF header parsing, for the details deployment tooling checks before running a binary.
+
+use crate::{
+    bytes::{u16_be, u16_le, u32_be, u32_le, u64_be, u64_le},
+    Sample, Strength,
+};
+use std::convert::TryFrom;
+
+pub(crate) const MAGIC: &[u8] = b"\x7fELF";
+
+const EI_CLASS: usize = 4;
+const EI_DATA: usize = 5;
+const EI_VERSION: usize = 6;
+const EV_CURRENT: u8 = 1;
+const PT_INTERP: u32 = 3;
+/// Program headers looked through for `PT_INTERP`, so a corrupt count can't stall us.
+const MAX_PROGRAM_HEADERS: usize = 256;
+
+/// Bytes a validator needs to see the whole of a 64-bit header.
+pub(crate) const HEAD_LEN: usize = 64;
+
+/// Byte order of a binary's multi-byte fields.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    pub(crate) fn u16(self, bytes: &[u8], offset: usize) -> Option<u16> {
+        match self {
+            Endianness::Little => u16_le(bytes, offset),
+            Endianness::Big => u16_be(bytes, offset),
+        }
+    }
+
+    pub(crate) fn u32(self, bytes: &[u8], offset: usize) -> Option<u32> {
+        match self {
+            Endianness::Little => u32_le(bytes, offset),
+            Endianness::Big => u32_be(bytes, offset),
+        }
+    }
+
+    pub(crate) fn u64(self, bytes: &[u8], offset: usize) -> Option<u64> {
+        match self {
+            Endianness::Little => u64_le(bytes, offset),
+            Endianness::Big => u64_be(bytes, offset),
+        }
+    }
+}
+
+/// Whether an ELF file is laid out for 32 or 64-bit addresses.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+/// What an ELF file is for, from its `e_type`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ElfKind {
+    /// An object file, for linking into something else.
+    Relocatable,
+    /// An executable at a fixed address.
+    Executable,
+    /// A shared library or, when [`Elf::interpreter`] is set, a position-independent
+    /// executable.
+    SharedObject,
+    Core,
+    /// An OS or processor-specific type.
+    Other(u16),
+}
+
+/// The architecture an ELF file was built for, from its `e_machine`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ElfMachine {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    RiscV,
+    PowerPc,
+    PowerPc64,
+    Mips,
+    S390,
+    Sparc,
+    Sparc64,
+    LoongArch,
+    /// Any other architecture, by its `e_machine` number.
+    Other(u16),
+}
+
+impl ElfMachine {
+    fn from_e_machine(machine: u16) -> Self {
+        match machine {
+            3 => ElfMachine::X86,
+            62 => ElfMachine::X86_64,
+            40 => ElfMachine::Arm,
+            183 => ElfMachine::Aarch64,
+            243 => ElfMachine::RiscV,
+            20 => ElfMachine::PowerPc,
+            21 => ElfMachine::PowerPc64,
+            8 => ElfMachine::Mips,
+            22 => ElfMachine::S390,
+            2 => ElfMachine::Sparc,
+            43 => ElfMachine::Sparc64,
+            258 => ElfMachine::LoongArch,
+            other => ElfMachine::Other(other),
+        }
+    }
+}
+
+/// The details of an ELF binary that say where it can run.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Elf {
+    pub class: ElfClass,
+    pub endianness: Endianness,
+    pub machine: ElfMachine,
+    pub kind: ElfKind,
+    /// Whether a `PT_INTERP` program header names a dynamic linker, which is what makes a
+    /// binary dynamically linked rather than static.
+    ///
+    /// This is `false` if the program headers are past the end of the input.
+    pub interpreter: bool,
+}
+
+impl Elf {
+    /// Parses the ELF header `bytes` starts with, or returns `None` if it isn't an ELF file.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(MAGIC) || *bytes.get(EI_VERSION)? != EV_CURRENT {
+            return None;
+        }
+
+        let class = match bytes[EI_CLASS] {
+            1 => ElfClass::Elf32,
+            2 => ElfClass::Elf64,
+            _ => return None,
+        };
+        let endianness = match bytes[EI_DATA] {
+            1 => Endianness::Little,
+            2 => Endianness::Big,
+            _ => return None,
+        };
+
+        let kind = match endianness.u16(bytes, 0x10)? {
+            1 => ElfKind::Relocatable,
+            2 => ElfKind::Executable,
+            3 => ElfKind::SharedObject,
+            4 => ElfKind::Core,
+            other => ElfKind::Other(other),
+        };
+        let machine = ElfMachine::from_e_machine(endianness.u16(bytes, 0x12)?);
+
+        // Offset of the program header table, then the size and number of its entries.
+        let (phoff, phentsize, phnum) = match class {
+            ElfClass::Elf32 => (
+                u64::from(endianness.u32(bytes, 0x1c)?),
+                endianness.u16(bytes, 0x2a)?,
+                endianness.u16(bytes, 0x2c)?,
+            ),
+            ElfClass::Elf64 => (
+                endianness.u64(bytes, 0x20)?,
+                endianness.u16(bytes, 0x36)?,
+                endianness.u16(bytes, 0x38)?,
+            ),
+        };
+
+        Some(Elf {
+            class,
+            endianness,
+            machine,
+            kind,
+            interpreter: has_interpreter(bytes, endianness, phoff, phentsize, phnum),
+        })
+    }
+}
+
+fn has_interpreter(
+    bytes: &[u8],
+    endianness: Endianness,
+    phoff: u64,
+    phentsize: u16,
+    phnum: u16,
+) -> bool {
+    let phoff = match usize::try_from(phoff) {
+        Ok(phoff) => phoff,
+        Err(_) => return false,
+    };
+
+    (0..usize::from(phnum).min(MAX_PROGRAM_HEADERS))
+        .map_while(|i| endianness.u32(bytes, phoff.checked_add(i * usize::from(phentsize))?))
+        .any(|p_type| p_type == PT_INTERP)
+}
+
+/// Checks the identification bytes hold a known class, byte order and version.
+pub(crate) fn validate(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < HEAD_LEN {
+        match sample.head.get(EI_CLASS..=EI_VERSION) {
+            Some([1..=2, 1..=2, EV_CURRENT]) | None => return Some(Strength::Weak),
+            Some(_) => return None,
+        }
+    }
+
+    Elf::parse(sample.head).map(|_| Strength::Strong)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Elf, ElfClass, ElfKind, ElfMachine, Endianness};
+    use std::{fs, io};
+
+    #[test]
+    fn parse() -> io::Result<()> {
+        assert_eq!(
+            Elf::parse(&fs::read("test.elf")?),
+            Some(Elf {
+                class: ElfClass::Elf64,
+                endianness: Endianness::Little,
+                machine: ElfMachine::X86_64,
+                kind: ElfKind::SharedObject,
+                interpreter: true,
+            })
+        );
+        assert_eq!(
+            Elf::parse(&fs::read("test-static.elf")?),
+            Some(Elf {
+                class: ElfClass::Elf32,
+                endianness: Endianness::Big,
+                machine: ElfMachine::Mips,
+                kind: ElfKind::Executable,
+                interpreter: false,
+            })
+        );
+
+        assert_eq!(Elf::parse(b"\x7fELF\x03\x01\x01"), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! Validation for fonts: the SFNT table directory TrueType and OpenType fonts start with,
+//! the collections and WOFF wrappers built around it, and PostScript Type 1 fonts.
+
+use crate::{
+    bytes::{u16_be, u32_be, u32_le},
+    Sample, Strength,
+};
+
+pub(crate) const TRUETYPE_MAGIC: &[u8] = &[0, 1, 0, 0];
+/// The version Apple's TrueType fonts use in place of 1.0.
+pub(crate) const APPLE_TRUETYPE_MAGIC: &[u8] = b"true";
+pub(crate) const OPENTYPE_MAGIC: &[u8] = b"OTTO";
+pub(crate) const COLLECTION_MAGIC: &[u8] = b"ttcf";
+pub(crate) const WOFF_MAGIC: &[u8] = b"wOFF";
+pub(crate) const WOFF2_MAGIC: &[u8] = b"wOF2";
+pub(crate) const PFA_MAGIC: &[u8] = b"%!PS-AdobeFont-";
+/// The other first line a PFA font may have, as FontForge writes it.
+pub(crate) const PFA_FONTTYPE_MAGIC: &[u8] = b"%!FontType1-";
+/// The marker and type of a PFB file's first segment, which holds ASCII.
+pub(crate) const PFB_MAGIC: &[u8] = &[0x80, 0x01];
+
+const SFNT_HEADER_LEN: usize = 12;
+const TABLE_RECORD_LEN: usize = 16;
+/// Most tables a font is taken to have. Real fonts have a few dozen at most.
+const MAX_TABLES: u16 = 128;
+const COLLECTION_HEADER_LEN: usize = 12;
+const WOFF_HEADER_LEN: u32 = 44;
+const WOFF2_HEADER_LEN: u32 = 48;
+const PFB_SEGMENT_HEADER_LEN: usize = 6;
+
+/// How far into an input a table directory, or a collection's first one, is read.
+pub(crate) const HEAD_LEN: usize = 4096;
+
+fn is_sfnt_version(bytes: &[u8]) -> bool {
+    [TRUETYPE_MAGIC, APPLE_TRUETYPE_MAGIC, OPENTYPE_MAGIC].contains(&bytes)
+}
+
+/// Checks each table at `start` has a printable tag and data past the directory.
+fn validate_table_directory(sample: &Sample<'_>, start: usize) -> Option<Strength> {
+    let head = sample.head;
+    let count = match head.get(start..).and_then(|directory| u16_be(directory, 4)) {
+        Some(0) => return None,
+        Some(count) if count > MAX_TABLES => return None,
+        Some(count) => usize::from(count),
+        None => return Some(Strength::Weak),
+    };
+    let data_start = start + SFNT_HEADER_LEN + TABLE_RECORD_LEN * count;
+
+    for table in 0..count {
+        let pos = start + SFNT_HEADER_LEN + TABLE_RECORD_LEN * table;
+        let fields = (
+            head.get(pos..pos + 4),
+            u32_be(head, pos + 8),
+            u32_be(head, pos + 12),
+        );
+        let (tag, offset, len) = match fields {
+            (Some(tag), Some(offset), Some(len)) => (tag, offset, len),
+            _ => return Some(Strength::Weak),
+        };
+
+        let printable = tag.iter().all(|byte| (0x20..=0x7e).contains(byte));
+        let past_end = sample.tail.is_some() && u64::from(offset) + u64::from(len) > sample.len;
+        if !printable || (offset as usize) < data_start || past_end {
+            return None;
+        }
+    }
+
+    Some(Strength::Strong)
+}
+
+pub(crate) fn validate_sfnt(sample: &Sample<'_>) -> Option<Strength> {
+    validate_table_directory(sample, 0)
+}
+
+/// Checks the `ttcf` version and font count, then the first font's table directory.
+pub(crate) fn validate_collection(sample: &Sample<'_>) -> Option<Strength> {
+    let head = sample.head;
+    let fields = (
+        u16_be(head, 4),
+        u16_be(head, 6),
+        u32_be(head, 8),
+        u32_be(head, 12),
+    );
+    let (major, minor, fonts, first) = match fields {
+        (Some(major), Some(minor), Some(fonts), Some(first)) => (major, minor, fonts, first),
+        _ => return Some(Strength::Weak),
+    };
+
+    let offsets_end = COLLECTION_HEADER_LEN as u64 + 4 * u64::from(fonts);
+    if !(1..=2).contains(&major) || minor != 0 || fonts == 0 || u64::from(first) < offsets_end {
+        return None;
+    }
+
+    match head.get(first as usize..).and_then(|font| font.get(..4)) {
+        Some(version) if is_sfnt_version(version) => {
+            validate_table_directory(sample, first as usize)
+        }
+        Some(_) => None,
+        None => Some(Strength::Weak),
+    }
+}
+
+/// Checks the wrapped flavour, table count, reserved field and declared length.
+fn validate_woff_header(
+    sample: &Sample<'_>,
+    header_len: u32,
+    collections: bool,
+) -> Option<Strength> {
+    let head = sample.head;
+    let fields = (
+        head.get(4..8),
+        u32_be(head, 8),
+        u16_be(head, 12),
+        u16_be(head, 14),
+    );
+    let (flavor, len, tables, reserved) = match fields {
+        (Some(flavor), Some(len), Some(tables), Some(reserved)) => (flavor, len, tables, reserved),
+        _ => return Some(Strength::Weak),
+    };
+
+    let known_flavor = is_sfnt_version(flavor) || (collections && flavor == COLLECTION_MAGIC);
+    let wrong_len = sample.tail.is_some() && u64::from(len) != sample.len;
+    if !known_flavor || len < header_len || tables == 0 || reserved != 0 || wrong_len {
+        return None;
+    }
+
+    Some(Strength::Strong)
+}
+
+pub(crate) fn validate_woff(sample: &Sample<'_>) -> Option<Strength> {
+    validate_woff_header(sample, WOFF_HEADER_LEN, false)
+}
+
+/// As for [`validate_woff`], though WOFF2 can also wrap a font collection.
+pub(crate) fn validate_woff2(sample: &Sample<'_>) -> Option<Strength> {
+    validate_woff_header(sample, WOFF2_HEADER_LEN, true)
+}
+
+/// Checks the first segment of a PFB font is non-empty and starts the way a PFA font does.
+pub(crate) fn validate_pfb(sample: &Sample<'_>) -> Option<Strength> {
+    let head = sample.head;
+    match u32_le(head, 2) {
+        Some(0) => return None,
+        Some(_) => {}
+        None => return Some(Strength::Weak),
+    }
+
+    let ascii = &head[PFB_SEGMENT_HEADER_LEN..];
+    if ascii.starts_with(PFA_MAGIC) || ascii.starts_with(PFA_FONTTYPE_MAGIC) {
+        Some(Strength::Strong)
+    } else if PFA_MAGIC.starts_with(ascii) || PFA_FONTTYPE_MAGIC.starts_with(ascii) {
+        Some(Strength::Weak)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,152 @@
+//! Structural validation for image formats whose magic numbers are too short to trust.
+
+use crate::{
+    bytes::{u16_be, u16_le, u32_be, u32_le},
+    Sample, Strength,
+};
+
+/// Sizes of the known `BITMAPINFOHEADER` variants, from `BITMAPCOREHEADER` to
+/// `BITMAPV5HEADER`.
+const BMP_DIB_HEADER_SIZES: &[u32] = &[12, 16, 40, 52, 56, 64, 108, 124];
+
+const ICON_HEADER_LEN: usize = 6;
+const ICON_ENTRY_LEN: usize = 16;
+/// Icon directory entries checked before trusting the rest.
+const ICON_ENTRIES_CHECKED: usize = 4;
+pub(crate) const ICON_HEAD_LEN: usize = ICON_HEADER_LEN + ICON_ENTRY_LEN * ICON_ENTRIES_CHECKED;
+
+fn strength(strong: bool) -> Option<Strength> {
+    Some(if strong {
+        Strength::Strong
+    } else {
+        Strength::Weak
+    })
+}
+
+fn bmp_header_consistent(sample: &Sample<'_>) -> Option<bool> {
+    let file_size = u32_le(sample.head, 2)?;
+    let reserved = u32_le(sample.head, 6)?;
+    let pixel_offset = u32_le(sample.head, 10)?;
+    let dib_size = u32_le(sample.head, 14)?;
+
+    Some(
+        (sample.tail.is_none() || u64::from(file_size) == sample.len)
+            && reserved == 0
+            && BMP_DIB_HEADER_SIZES.contains(&dib_size)
+            && pixel_offset >= 14 + dib_size
+            && pixel_offset <= file_size,
+    )
+}
+
+/// Checks that the BMP file header agrees with the input's length, if that's known, and is
+/// followed by a known DIB header.
+pub(crate) fn validate_bmp(sample: &Sample<'_>) -> Option<Strength> {
+    strength(bmp_header_consistent(sample).unwrap_or(false))
+}
+
+/// Checks that the SOI marker is followed by well-formed marker segments, up to the start
+/// of the scan data or as far as the input goes.
+pub(crate) fn validate_jpeg(sample: &Sample<'_>) -> Option<Strength> {
+    let head = sample.head;
+    let mut pos = 2;
+    let mut segments = 0;
+
+    while pos < head.len() {
+        if head[pos] != 0xff {
+            return strength(false);
+        }
+
+        // Any number of 0xff fill bytes may come before the marker itself.
+        while head.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+
+        let marker = match head.get(pos + 1) {
+            Some(&marker) => marker,
+            None => break,
+        };
+
+        // Standalone markers (TEM, RSTn, SOI, EOI) can't appear before the first scan.
+        if marker < 0xc0 || (0xd0..=0xd9).contains(&marker) {
+            return strength(false);
+        }
+
+        let len = match u16_be(head, pos + 2) {
+            Some(len) if len >= 2 => usize::from(len),
+            Some(_) => return strength(false),
+            None => break,
+        };
+
+        segments += 1;
+
+        // Start of scan: what follows is entropy-coded data, not more segments.
+        if marker == 0xda {
+            break;
+        }
+
+        pos += 2 + len;
+    }
+
+    strength(segments > 0)
+}
+
+/// Checks the first few entries of an icon or cursor directory. The signature is four
+/// bytes, two of them zero, so anything inconsistent is rejected rather than kept as weak.
+fn validate_icon_directory(sample: &Sample<'_>, cursor: bool) -> Option<Strength> {
+    let head = sample.head;
+    let count = match u16_le(head, 4) {
+        Some(0) => return None,
+        Some(count) => usize::from(count),
+        None => return strength(false),
+    };
+    let data_start = ICON_HEADER_LEN + ICON_ENTRY_LEN * count;
+
+    for entry in 0..count.min(ICON_ENTRIES_CHECKED) {
+        let pos = ICON_HEADER_LEN + ICON_ENTRY_LEN * entry;
+        let fields = (
+            head.get(pos + 3),
+            u16_le(head, pos + 4),
+            u32_le(head, pos + 8),
+            u32_le(head, pos + 12),
+        );
+        let (&reserved, planes, size, offset) = match fields {
+            (Some(reserved), Some(planes), Some(size), Some(offset)) => {
+                (reserved, planes, size, offset)
+            }
+            _ => return strength(false),
+        };
+
+        // Cursors keep the hotspot where icons have the colour planes.
+        let planes_ok = cursor || planes <= 1;
+        if reserved != 0 || !planes_ok || size == 0 || (offset as usize) < data_start {
+            return None;
+        }
+    }
+
+    strength(true)
+}
+
+pub(crate) fn validate_ico(sample: &Sample<'_>) -> Option<Strength> {
+    validate_icon_directory(sample, false)
+}
+
+pub(crate) fn validate_cur(sample: &Sample<'_>) -> Option<Strength> {
+    validate_icon_directory(sample, true)
+}
+
+/// Checks the QOI header has a non-empty size and defined channel and colour space values.
+pub(crate) fn validate_qoi(sample: &Sample<'_>) -> Option<Strength> {
+    let head = sample.head;
+    let fields = (u32_be(head, 4), u32_be(head, 8), head.get(12), head.get(13));
+
+    match fields {
+        (Some(width), Some(height), Some(channels), Some(colorspace)) => {
+            if width > 0 && height > 0 && (3..=4).contains(channels) && *colorspace <= 1 {
+                strength(true)
+            } else {
+                None
+            }
+        }
+        _ => strength(false),
+    }
+}
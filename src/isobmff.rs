@@ -0,0 +1,453 @@
+//! ISO base media files (MP4, QuickTime, HEIF and relatives), which all share one box
+//! structure and say what they are through the brands in their `ftyp` box.
+
+use crate::{
+    bytes::{u16_be, u32_be, u64_be},
+    Codec, FileType, Sample, Strength, Track,
+};
+use std::convert::{TryFrom, TryInto};
+
+/// Size and type of a box, before its contents.
+const BOX_HEADER_LEN: usize = 8;
+/// Smallest `ftyp` box: the header, major brand and minor version.
+const FTYP_MIN_LEN: usize = BOX_HEADER_LEN + 8;
+
+/// How far into an input compatible brands are read.
+pub(crate) const HEAD_LEN: usize = 256;
+
+/// Boxes leading from the top level down to the sample descriptions of each track.
+const SAMPLE_DESCRIPTIONS: &[&[u8; 4]] = &[b"moov", b"trak", b"mdia", b"minf", b"stbl", b"stsd"];
+/// Version and flags of a full box, then the entry count, before the `stsd` entries.
+const STSD_HEADER_LEN: usize = 8;
+/// Fields every sample entry starts with, before those for its kind of track.
+const SAMPLE_ENTRY_LEN: usize = 8;
+const VISUAL_SAMPLE_ENTRY_LEN: usize = SAMPLE_ENTRY_LEN + 70;
+const AUDIO_SAMPLE_ENTRY_LEN: usize = SAMPLE_ENTRY_LEN + 20;
+/// Fields QuickTime's version 1 and 2 sound descriptions add to an audio sample entry.
+const QUICKTIME_SOUND_EXTRA_LEN: [usize; 3] = [0, 16, 36];
+
+/// MPEG-4 descriptor tags in an `esds` box.
+const ES_DESCRIPTOR: u8 = 0x03;
+const DECODER_CONFIG_DESCRIPTOR: u8 = 0x04;
+const DECODER_SPECIFIC_INFO: u8 = 0x05;
+/// Fields of a decoder config descriptor before the descriptors it holds.
+const DECODER_CONFIG_LEN: usize = 13;
+/// Object type indications for MPEG-4 audio and the MPEG-2 AAC profiles.
+const AAC_OBJECT_TYPES: &[u8] = &[0x40, 0x66, 0x67, 0x68];
+/// Object type indications for MPEG-1 and MPEG-2 audio, layers I to III.
+const MPEG_AUDIO_OBJECT_TYPES: &[u8] = &[0x69, 0x6b];
+
+/// The `ftyp` box at the start of an ISO base media file, naming the specifications the
+/// file conforms to.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Ftyp {
+    /// The specification the file is best used with.
+    pub major_brand: [u8; 4],
+    pub minor_version: u32,
+    /// Other specifications the file also conforms to, usually including the major brand.
+    ///
+    /// Brands past the end of the input are left out.
+    pub compatible_brands: Vec<[u8; 4]>,
+}
+
+impl Ftyp {
+    /// Parses the `ftyp` box `bytes` starts with, or returns `None` if there isn't one.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let size = u32_be(bytes, 0)? as usize;
+        if bytes.get(4..8)? != b"ftyp" || size < FTYP_MIN_LEN || size % 4 != 0 {
+            return None;
+        }
+
+        let brands = bytes.get(FTYP_MIN_LEN..size.min(bytes.len()))?;
+
+        Some(Ftyp {
+            major_brand: bytes[8..12].try_into().ok()?,
+            minor_version: u32_be(bytes, 12)?,
+            compatible_brands: brands
+                .chunks_exact(4)
+                .filter_map(|brand| brand.try_into().ok())
+                .collect(),
+        })
+    }
+
+    /// The type the brands describe, if any of them are known.
+    ///
+    /// A specific major brand such as `heic` decides on its own. Otherwise the first
+    /// specific compatible brand does, and failing that the generic HEIF and MP4 brands
+    /// give [`FileType::Heif`] and [`FileType::Mp4`]. A generic MP4 major brand outranks
+    /// compatible 3GPP brands, which phones and ffmpeg often list in plain MP4 files.
+    pub fn file_type(&self) -> Option<FileType> {
+        let brands = || std::iter::once(&self.major_brand).chain(&self.compatible_brands);
+        let mp4_major = is_mp4_brand(&self.major_brand);
+
+        brands()
+            .filter_map(specific_brand)
+            .find(|file_type| {
+                !(mp4_major && matches!(file_type, FileType::ThreeGp | FileType::ThreeG2))
+            })
+            .or_else(|| brands().any(is_heif_brand).then_some(FileType::Heif))
+            .or_else(|| brands().any(is_mp4_brand).then_some(FileType::Mp4))
+    }
+}
+
+/// Brands that pin down one type.
+fn specific_brand(brand: &[u8; 4]) -> Option<FileType> {
+    Some(match brand {
+        b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" | b"hevm" | b"hevs" => {
+            FileType::Heic
+        }
+        b"avif" | b"avis" => FileType::Avif,
+        b"M4A " | b"M4B " | b"M4P " => FileType::M4a,
+        b"M4V " | b"M4VH" | b"M4VP" => FileType::M4v,
+        b"qt  " => FileType::Mov,
+        [b'3', b'g', b'2', _] => FileType::ThreeG2,
+        [b'3', b'g', _, _] => FileType::ThreeGp,
+        _ => return None,
+    })
+}
+
+/// Brands shared by every HEIF image, whatever it's coded with.
+fn is_heif_brand(brand: &[u8; 4]) -> bool {
+    matches!(brand, b"mif1" | b"mif2" | b"msf1" | b"miaf")
+}
+
+fn is_mp4_brand(brand: &[u8; 4]) -> bool {
+    matches!(
+        brand,
+        [b'i', b's', b'o', _] | b"mp41" | b"mp42" | b"mp71" | b"avc1" | b"dash" | b"f4v " | b"mmp4"
+    )
+}
+
+/// Accepts `ftyp` boxes whose brands name a type we know.
+pub(crate) fn validate_ftyp(sample: &Sample<'_>) -> Option<Strength> {
+    subtype(sample).map(|_| Strength::Strong)
+}
+
+pub(crate) fn subtype(sample: &Sample<'_>) -> Option<FileType> {
+    Ftyp::parse(sample.head)?.file_type()
+}
+
+/// Iterates over the boxes laid end to end in `bytes`, yielding each one's type and
+/// contents. A box cut off by the end of `bytes` is yielded as far as it goes.
+fn boxes(bytes: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        let size = u32_be(bytes, pos)?;
+        let box_type = bytes.get(pos + 4..pos + BOX_HEADER_LEN)?.try_into().ok()?;
+        let (header, size) = match size {
+            0 => (BOX_HEADER_LEN, bytes.len() - pos),
+            1 => (
+                BOX_HEADER_LEN + 8,
+                usize::try_from(u64_be(bytes, pos + BOX_HEADER_LEN)?).ok()?,
+            ),
+            size => (BOX_HEADER_LEN, size as usize),
+        };
+        if size < header {
+            return None;
+        }
+
+        let end = pos.checked_add(size)?;
+        let contents = bytes.get(pos + header..end.min(bytes.len()))?;
+        pos = end;
+
+        Some((box_type, contents))
+    })
+}
+
+fn child<'a>(bytes: &'a [u8], wanted: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(bytes)
+        .find(|(box_type, _)| box_type == wanted)
+        .map(|(_, contents)| contents)
+}
+
+/// The sample entries describing each track's coding, as their format and contents.
+fn sample_entries(bytes: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let descriptions = SAMPLE_DESCRIPTIONS
+        .iter()
+        .fold(vec![bytes], |parents, wanted| {
+            parents
+                .into_iter()
+                .flat_map(|parent| boxes(parent).filter(|(box_type, _)| box_type == *wanted))
+                .map(|(_, contents)| contents)
+                .collect()
+        });
+
+    descriptions
+        .into_iter()
+        .filter_map(|stsd| stsd.get(STSD_HEADER_LEN..))
+        .flat_map(boxes)
+        .collect()
+}
+
+/// The tracks of the movie in `bytes`, in the codecs we know.
+pub(crate) fn tracks(bytes: &[u8]) -> Vec<Track> {
+    sample_entries(bytes)
+        .into_iter()
+        .filter_map(|(format, entry)| track(&format, entry))
+        .collect()
+}
+
+fn track(format: &[u8; 4], entry: &[u8]) -> Option<Track> {
+    let fourcc = std::str::from_utf8(format).ok()?;
+    let visual = entry.get(VISUAL_SAMPLE_ENTRY_LEN..);
+    let config = |wanted| child(visual?, wanted);
+
+    let (codec, codecs) = match format {
+        b"avc1" | b"avc3" => (
+            Codec::Avc,
+            config(b"avcC").and_then(|avcc| avc_codecs(fourcc, avcc)),
+        ),
+        b"hvc1" | b"hev1" => (
+            Codec::Hevc,
+            config(b"hvcC").and_then(|hvcc| hevc_codecs(fourcc, hvcc)),
+        ),
+        b"av01" => (Codec::Av1, config(b"av1C").and_then(av1_codecs)),
+        b"vp09" => (Codec::Vp9, config(b"vpcC").and_then(vp9_codecs)),
+        b"mp4a" => return mp4a_track(audio_config(entry, b"esds")?),
+        b"Opus" => (Codec::Opus, Some("opus".to_string())),
+        b"fLaC" => (Codec::Flac, Some("flac".to_string())),
+        _ => return None,
+    };
+
+    // Without its configuration box, a video track can still be named by its format.
+    Some(Track {
+        codec,
+        codecs: codecs.unwrap_or_else(|| fourcc.to_string()),
+    })
+}
+
+/// Finds a box among an audio sample entry's children, or in QuickTime's `wave` box.
+fn audio_config<'a>(entry: &'a [u8], wanted: &[u8; 4]) -> Option<&'a [u8]> {
+    let version = usize::from(u16_be(entry, SAMPLE_ENTRY_LEN)?);
+    let extra = QUICKTIME_SOUND_EXTRA_LEN.get(version)?;
+    let children = entry.get(AUDIO_SAMPLE_ENTRY_LEN + extra..)?;
+
+    child(children, wanted).or_else(|| child(child(children, b"wave")?, wanted))
+}
+
+/// `avc1.PPCCLL`: profile, constraint flags and level, in hex.
+fn avc_codecs(fourcc: &str, avcc: &[u8]) -> Option<String> {
+    let fields = avcc.get(1..4)?;
+
+    Some(format!(
+        "{}.{:02x}{:02x}{:02x}",
+        fourcc, fields[0], fields[1], fields[2]
+    ))
+}
+
+/// `hvc1.1.6.L93.B0`: profile space and profile, compatibility flags in reverse bit
+/// order, tier and level, then the constraint bytes without trailing zeros.
+fn hevc_codecs(fourcc: &str, hvcc: &[u8]) -> Option<String> {
+    let fields = hvcc.get(..13)?;
+    let space = ["", "A", "B", "C"][usize::from(fields[1] >> 6)];
+    let tier = if fields[1] & 0x20 == 0 { 'L' } else { 'H' };
+    let compatibility = u32_be(fields, 2)?.reverse_bits();
+
+    let mut constraints = &fields[6..12];
+    while let [rest @ .., 0] = constraints {
+        constraints = rest;
+    }
+
+    let mut codecs = format!(
+        "{}.{}{}.{:X}.{}{}",
+        fourcc,
+        space,
+        fields[1] & 0x1f,
+        compatibility,
+        tier,
+        fields[12]
+    );
+    for byte in constraints {
+        codecs += &format!(".{:X}", byte);
+    }
+
+    Some(codecs)
+}
+
+/// `av01.0.04M.08`: profile, level and tier, and bit depth.
+fn av1_codecs(av1c: &[u8]) -> Option<String> {
+    let (profile, flags) = (av1c.get(1)?, av1c.get(2)?);
+    let tier = if flags & 0x80 == 0 { 'M' } else { 'H' };
+    let depth = match (flags & 0x40 != 0, flags & 0x20 != 0) {
+        (false, _) => 8,
+        (true, false) => 10,
+        (true, true) => 12,
+    };
+
+    Some(format!(
+        "av01.{}.{:02}{}.{:02}",
+        profile >> 5,
+        profile & 0x1f,
+        tier,
+        depth
+    ))
+}
+
+/// `vp09.00.10.08`: profile, level and bit depth.
+fn vp9_codecs(vpcc: &[u8]) -> Option<String> {
+    let fields = vpcc.get(4..7)?;
+
+    Some(format!(
+        "vp09.{:02}.{:02}.{:02}",
+        fields[0],
+        fields[1],
+        fields[2] >> 4
+    ))
+}
+
+/// Reads the MPEG-4 descriptor with `tag` at the start of `bytes`, returning its contents.
+fn descriptor(bytes: &[u8], tag: u8) -> Option<&[u8]> {
+    if *bytes.first()? != tag {
+        return None;
+    }
+
+    // The length takes up to four bytes, seven bits at a time.
+    let mut len = 0;
+    let mut pos = 1;
+    loop {
+        let byte = *bytes.get(pos)?;
+        len = len << 7 | usize::from(byte & 0x7f);
+        pos += 1;
+        if byte & 0x80 == 0 || pos == 5 {
+            break;
+        }
+    }
+
+    bytes.get(pos..(pos + len).min(bytes.len()))
+}
+
+/// `mp4a.40.2`: the object type indication in hex, then for MPEG-4 audio the audio object
+/// type from the decoder specific info.
+fn mp4a_track(esds: &[u8]) -> Option<Track> {
+    let es = descriptor(esds.get(4..)?, ES_DESCRIPTOR)?;
+    let flags = *es.get(2)?;
+    let mut pos = 3;
+    if flags & 0x80 != 0 {
+        pos += 2;
+    }
+    if flags & 0x40 != 0 {
+        pos += 1 + usize::from(*es.get(pos)?);
+    }
+    if flags & 0x20 != 0 {
+        pos += 2;
+    }
+
+    let config = descriptor(es.get(pos..)?, DECODER_CONFIG_DESCRIPTOR)?;
+    let object_type = *config.first()?;
+    let codec = if AAC_OBJECT_TYPES.contains(&object_type) {
+        Codec::Aac
+    } else if MPEG_AUDIO_OBJECT_TYPES.contains(&object_type) {
+        Codec::Mp3
+    } else {
+        return None;
+    };
+
+    let mut codecs = format!("mp4a.{:02X}", object_type);
+    if object_type == 0x40 {
+        let info = config
+            .get(DECODER_CONFIG_LEN..)
+            .and_then(|rest| descriptor(rest, DECODER_SPECIFIC_INFO));
+        // Audio object types from 32 up escape to six more bits.
+        let audio_object_type = info.and_then(|info| match info.first()? >> 3 {
+            31 => Some(32 + (u16_be(info, 0)? >> 5 & 0x3f) as u8),
+            audio_object_type => Some(audio_object_type),
+        });
+        if let Some(audio_object_type) = audio_object_type {
+            codecs += &format!(".{}", audio_object_type);
+        }
+    }
+
+    Some(Track { codec, codecs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{av1_codecs, hevc_codecs, vp9_codecs, Ftyp};
+    use crate::FileType;
+    use std::{fs, io};
+
+    fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let mut bytes = ((16 + compatible.len() * 4) as u32).to_be_bytes().to_vec();
+        bytes.extend(b"ftyp");
+        bytes.extend(major);
+        bytes.extend(&[0; 4]);
+        for brand in compatible {
+            bytes.extend(*brand);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse() -> io::Result<()> {
+        assert_eq!(
+            Ftyp::parse(&fs::read("test.mp4")?),
+            Some(Ftyp {
+                major_brand: *b"isom",
+                minor_version: 0x200,
+                compatible_brands: vec![*b"isom", *b"iso2", *b"avc1", *b"mp41"],
+            })
+        );
+
+        // Brands cut off by the end of the input are dropped.
+        let bytes = ftyp(b"heic", &[b"mif1", b"heic"]);
+        assert_eq!(
+            Ftyp::parse(&bytes[..22]).map(|ftyp| ftyp.compatible_brands),
+            Some(vec![*b"mif1"])
+        );
+
+        assert_eq!(Ftyp::parse(&bytes[..15]), None);
+        assert_eq!(Ftyp::parse(b"\0\0\0\x11ftypheic\0\0\0\0mif1"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn codecs_strings() {
+        assert_eq!(
+            hevc_codecs("hvc1", &[1, 0x01, 0x60, 0, 0, 0, 0xb0, 0, 0, 0, 0, 0, 93]).as_deref(),
+            Some("hvc1.1.6.L93.B0")
+        );
+        assert_eq!(
+            hevc_codecs("hev1", &[1, 0x22, 0x20, 0, 0, 0, 0x90, 0, 0, 0, 0, 0, 120]).as_deref(),
+            Some("hev1.2.4.H120.90")
+        );
+        assert_eq!(
+            av1_codecs(&[0x81, 0x04, 0x0c, 0]).as_deref(),
+            Some("av01.0.04M.08")
+        );
+        assert_eq!(
+            av1_codecs(&[0x81, 0x2d, 0xc0, 0]).as_deref(),
+            Some("av01.1.13H.10")
+        );
+        assert_eq!(
+            vp9_codecs(&[1, 0, 0, 0, 0, 10, 0x80]).as_deref(),
+            Some("vp09.00.10.08")
+        );
+    }
+
+    #[test]
+    fn brands() {
+        let file_type = |major, compatible| Ftyp::parse(&ftyp(major, compatible))?.file_type();
+
+        assert_eq!(
+            file_type(b"mif1", &[b"mif1", b"heic"]),
+            Some(FileType::Heic)
+        );
+        assert_eq!(
+            file_type(b"mif1", &[b"avif", b"miaf"]),
+            Some(FileType::Avif)
+        );
+        assert_eq!(file_type(b"msf1", &[b"iso8"]), Some(FileType::Heif));
+        assert_eq!(file_type(b"mp42", &[b"isom", b"M4A "]), Some(FileType::M4a));
+        assert_eq!(file_type(b"3gp5", &[]), Some(FileType::ThreeGp));
+        assert_eq!(file_type(b"mp42", &[b"mp42", b"isom"]), Some(FileType::Mp4));
+        assert_eq!(file_type(b"mp42", &[b"isom", b"3gp4"]), Some(FileType::Mp4));
+        assert_eq!(file_type(b"isom", &[b"3g2a", b"avc1"]), Some(FileType::Mp4));
+        assert_eq!(
+            file_type(b"3gp4", &[b"isom", b"mp42"]),
+            Some(FileType::ThreeGp)
+        );
+        assert_eq!(file_type(b"crx ", &[b"crx "]), None);
+    }
+}
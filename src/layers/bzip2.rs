@@ -0,0 +1,330 @@
+//! A bzip2 decoder. Each block has to be decoded whole, since the Burrows-Wheeler
+//! transform can't be undone piecemeal, so a block longer than the output limit isn't
+//! decoded at all. Decoding also stops after the block that reaches the limit.
+
+use super::Decoded;
+
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+const END_MAGIC: u64 = 0x1772_4538_5090;
+const MAX_GROUPS: usize = 6;
+const MAX_CODE_LEN: usize = 20;
+const GROUP_SIZE: usize = 50;
+const RUN_A: u16 = 0;
+const RUN_B: u16 = 1;
+
+/// Most-significant-bit-first reader.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Option<u32> {
+        let byte = *self.bytes.get(self.pos)?;
+        let bit = (byte >> (7 - self.bit)) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Some(u32::from(bit))
+    }
+
+    fn bits(&mut self, n: u32) -> Option<u64> {
+        (0..n).try_fold(0, |acc, _| Some(acc << 1 | u64::from(self.bit()?)))
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in code
+/// order.
+struct Huffman {
+    counts: [u16; MAX_CODE_LEN + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; MAX_CODE_LEN + 1];
+        for &len in lengths {
+            *counts.get_mut(usize::from(len))? += 1;
+        }
+
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for len in 1..=MAX_CODE_LEN as u8 {
+            symbols
+                .extend((0..lengths.len() as u16).filter(|&sym| lengths[usize::from(sym)] == len));
+        }
+
+        Some(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i64, 0i64, 0i64);
+
+        for &count in &self.counts[1..] {
+            code |= i64::from(bits.bit()?);
+            let count = i64::from(count);
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+/// Reads one block's Huffman-coded, move-to-front-transformed contents and undoes those
+/// stages, returning the block as it was after the Burrows-Wheeler transform along with
+/// the index of the original first rotation. Blocks longer than `max_len` give `None`.
+fn read_block(bits: &mut Bits<'_>, max_len: usize) -> Option<(Vec<u8>, usize)> {
+    let _crc = bits.bits(32)?;
+    if bits.bit()? != 0 {
+        // Randomised blocks haven't been written since bzip2 0.9.5.
+        return None;
+    }
+    let orig_ptr = bits.bits(24)? as usize;
+
+    let ranges = bits.bits(16)?;
+    let mut used = Vec::new();
+    for range in 0..16 {
+        if ranges & (1 << (15 - range)) != 0 {
+            let bytes = bits.bits(16)?;
+            used.extend(
+                (0..16)
+                    .filter(|i| bytes & (1 << (15 - i)) != 0)
+                    .map(|i| range * 16 + i),
+            );
+        }
+    }
+    if used.is_empty() {
+        return None;
+    }
+    let alphabet = used.len() + 2;
+
+    let groups = bits.bits(3)? as usize;
+    let selector_count = bits.bits(15)? as usize;
+    if !(2..=MAX_GROUPS).contains(&groups) || selector_count == 0 {
+        return None;
+    }
+
+    let mut group_mtf: Vec<u8> = (0..groups as u8).collect();
+    let mut selectors = Vec::with_capacity(selector_count);
+    for _ in 0..selector_count {
+        let mut index = 0;
+        while bits.bit()? == 1 {
+            index += 1;
+            if index >= groups {
+                return None;
+            }
+        }
+        let group = group_mtf.remove(index);
+        group_mtf.insert(0, group);
+        selectors.push(group);
+    }
+
+    let mut tables = Vec::with_capacity(groups);
+    for _ in 0..groups {
+        let mut len = bits.bits(5)? as i32;
+        let mut lengths = vec![0u8; alphabet];
+        for length in lengths.iter_mut() {
+            loop {
+                if !(1..=MAX_CODE_LEN as i32).contains(&len) {
+                    return None;
+                }
+                if bits.bit()? == 0 {
+                    break;
+                }
+                len += if bits.bit()? == 0 { 1 } else { -1 };
+            }
+            *length = len as u8;
+        }
+        tables.push(Huffman::new(&lengths)?);
+    }
+
+    let end_of_block = (alphabet - 1) as u16;
+    let mut mtf: Vec<u8> = used.iter().map(|&b| b as u8).collect();
+    let mut block = Vec::new();
+    let mut run = 0usize;
+    let mut run_weight = 1usize;
+    let mut decoded = 0;
+
+    loop {
+        let table = &tables[usize::from(*selectors.get(decoded / GROUP_SIZE)?)];
+        let symbol = table.decode(bits)?;
+        decoded += 1;
+
+        if symbol == RUN_A || symbol == RUN_B {
+            run += run_weight << symbol;
+            run_weight <<= 1;
+            if block.len() + run > max_len {
+                return None;
+            }
+            continue;
+        }
+
+        if run > 0 {
+            block.resize(block.len() + run, mtf[0]);
+            run = 0;
+            run_weight = 1;
+        }
+
+        if symbol == end_of_block {
+            break;
+        }
+
+        let byte = mtf.remove(usize::from(symbol - 1));
+        mtf.insert(0, byte);
+        block.push(byte);
+
+        if block.len() > max_len {
+            return None;
+        }
+    }
+
+    if orig_ptr >= block.len() {
+        return None;
+    }
+
+    Some((block, orig_ptr))
+}
+
+/// Undoes the Burrows-Wheeler transform, then the run-length encoding bzip2 applies before
+/// it, appending to `out` until it holds `limit` bytes.
+fn unsort(block: &[u8], orig_ptr: usize, out: &mut Vec<u8>, limit: usize) {
+    let mut starts = [0usize; 256];
+    for &byte in block {
+        starts[usize::from(byte)] += 1;
+    }
+    let mut total = 0;
+    for start in starts.iter_mut() {
+        let count = *start;
+        *start = total;
+        total += count;
+    }
+
+    let mut next = vec![0u32; block.len()];
+    for (i, &byte) in block.iter().enumerate() {
+        next[starts[usize::from(byte)]] = i as u32;
+        starts[usize::from(byte)] += 1;
+    }
+
+    let mut pos = next[orig_ptr] as usize;
+    let mut last = None;
+    let mut repeats = 0;
+
+    for _ in 0..block.len() {
+        if out.len() >= limit {
+            return;
+        }
+
+        let byte = block[pos];
+        pos = next[pos] as usize;
+
+        if repeats == 4 {
+            // After four equal bytes comes a count of how many more there are.
+            let extra = usize::from(byte).min(limit - out.len());
+            out.resize(out.len() + extra, last.unwrap_or(0));
+            repeats = 0;
+            last = None;
+            continue;
+        }
+
+        if Some(byte) == last {
+            repeats += 1;
+        } else {
+            repeats = 1;
+            last = Some(byte);
+        }
+        out.push(byte);
+    }
+}
+
+/// Decompresses a bzip2 stream, up to `limit` bytes of output.
+///
+/// A block is held twice over while it's decoded, once as bytes and once as a table of
+/// `u32` positions. Decoding ends, incomplete, at a block longer than `limit`, so memory
+/// and work grow with `limit` rather than with the stream's 100-900kB block size.
+pub(super) fn bunzip(input: &[u8], limit: usize) -> Option<Decoded> {
+    let level = match input.get(..4)? {
+        [b'B', b'Z', b'h', level @ b'1'..=b'9'] => usize::from(level - b'0'),
+        _ => return None,
+    };
+    let max_len = (level * 100_000).min(limit);
+
+    let mut bits = Bits {
+        bytes: input,
+        pos: 4,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+
+    loop {
+        if out.len() >= limit {
+            out.truncate(limit);
+            return Some(Decoded {
+                bytes: out,
+                complete: false,
+            });
+        }
+
+        let magic = bits.bits(48);
+        let block = match magic {
+            Some(BLOCK_MAGIC) => read_block(&mut bits, max_len),
+            Some(END_MAGIC) => {
+                return Some(Decoded {
+                    bytes: out,
+                    complete: true,
+                })
+            }
+            _ => None,
+        };
+
+        match block {
+            Some((block, orig_ptr)) => unsort(&block, orig_ptr, &mut out, limit),
+            None => {
+                return Some(Decoded {
+                    bytes: out,
+                    complete: false,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bunzip;
+    use std::{fs, io};
+
+    #[test]
+    fn empty_stream() -> io::Result<()> {
+        let decoded = bunzip(&fs::read("test.bz2")?, 1024).unwrap();
+        assert_eq!(decoded.bytes, b"");
+        assert!(decoded.complete);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tarball() -> io::Result<()> {
+        let tar = fs::read("test-ustar.tar")?;
+        let decoded = bunzip(&fs::read("test.tar.bz2")?, 1 << 20).unwrap();
+        assert_eq!(decoded.bytes, tar);
+        assert!(decoded.complete);
+
+        let decoded = bunzip(&fs::read("test.tar.bz2")?, 600).unwrap();
+        assert_eq!(decoded.bytes, &tar[..600]);
+        assert!(!decoded.complete);
+
+        // The one block is longer than this, so none of it is decoded.
+        let decoded = bunzip(&fs::read("test.tar.bz2")?, 256).unwrap();
+        assert_eq!(decoded.bytes, b"");
+        assert!(!decoded.complete);
+
+        Ok(())
+    }
+}
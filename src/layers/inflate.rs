@@ -0,0 +1,328 @@
+//! A small DEFLATE decoder (RFC 1951) for gzip members (RFC 1952), modelled on zlib's
+//! `puff`. It favours brevity over speed, which is fine for the prefixes detection needs.
+
+use super::Decoded;
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order code length code lengths are sent in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const GZIP_FHCRC: u8 = 1 << 1;
+const GZIP_FEXTRA: u8 = 1 << 2;
+const GZIP_FNAME: u8 = 1 << 3;
+const GZIP_FCOMMENT: u8 = 1 << 4;
+
+/// Least-significant-bit-first reader.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    buf: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Bits {
+            bytes,
+            pos: 0,
+            buf: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Option<u32> {
+        while self.count < n {
+            let byte = *self.bytes.get(self.pos)?;
+            self.pos += 1;
+            self.buf |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.count -= n;
+        Some(value)
+    }
+
+    /// Drops any bits left in the current byte.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in code
+/// order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+
+        // Over-subscribed codes can't be decoded; incomplete ones are allowed.
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return None;
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[usize::from(offsets[usize::from(len)])] = symbol as u16;
+                offsets[usize::from(len)] += 1;
+            }
+        }
+
+        Some(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits<'_>) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - count < first {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+struct Inflater<'a> {
+    bits: Bits<'a>,
+    out: Vec<u8>,
+    limit: usize,
+}
+
+impl Inflater<'_> {
+    fn full(&self) -> bool {
+        self.out.len() >= self.limit
+    }
+
+    fn stored(&mut self) -> Option<()> {
+        self.bits.align();
+        let bytes = self.bits.bytes;
+        let pos = self.bits.pos;
+
+        let len = usize::from(u16::from_le_bytes([*bytes.get(pos)?, *bytes.get(pos + 1)?]));
+        let nlen = u16::from_le_bytes([*bytes.get(pos + 2)?, *bytes.get(pos + 3)?]);
+        if len != usize::from(!nlen) {
+            return None;
+        }
+
+        let data = bytes.get(pos + 4..)?;
+        let take = len.min(data.len()).min(self.limit - self.out.len());
+        self.out.extend_from_slice(&data[..take]);
+        self.bits.pos = pos + 4 + len;
+
+        if take < len && !self.full() {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    fn codes(&mut self, lengths: &Huffman, distances: &Huffman) -> Option<()> {
+        loop {
+            if self.full() {
+                return Some(());
+            }
+
+            let symbol = usize::from(lengths.decode(&mut self.bits)?);
+            if symbol < 256 {
+                self.out.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Some(());
+            }
+
+            let symbol = symbol - 257;
+            let len = usize::from(*LENGTH_BASE.get(symbol)?)
+                + self.bits.bits(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+
+            let symbol = usize::from(distances.decode(&mut self.bits)?);
+            let dist = usize::from(*DIST_BASE.get(symbol)?)
+                + self.bits.bits(u32::from(DIST_EXTRA[symbol]))? as usize;
+
+            let start = self.out.len().checked_sub(dist)?;
+            for i in 0..len.min(self.limit - self.out.len()) {
+                let byte = self.out[start + i];
+                self.out.push(byte);
+            }
+        }
+    }
+
+    fn fixed(&mut self) -> Option<()> {
+        let mut lengths = [0u8; 288];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+
+        let lengths = Huffman::new(&lengths)?;
+        let distances = Huffman::new(&[5; 30])?;
+        self.codes(&lengths, &distances)
+    }
+
+    fn dynamic(&mut self) -> Option<()> {
+        let nlen = self.bits.bits(5)? as usize + 257;
+        let ndist = self.bits.bits(5)? as usize + 1;
+        let ncode = self.bits.bits(4)? as usize + 4;
+        if nlen > 286 || ndist > 30 {
+            return None;
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..ncode] {
+            code_lengths[index] = self.bits.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_lengths)?;
+
+        let mut lengths = vec![0u8; nlen + ndist];
+        let mut index = 0;
+        while index < nlen + ndist {
+            let symbol = code_lengths.decode(&mut self.bits)?;
+            if symbol < 16 {
+                lengths[index] = symbol as u8;
+                index += 1;
+                continue;
+            }
+
+            let (value, repeat) = match symbol {
+                16 => (*lengths.get(index.checked_sub(1)?)?, 3 + self.bits.bits(2)?),
+                17 => (0, 3 + self.bits.bits(3)?),
+                _ => (0, 11 + self.bits.bits(7)?),
+            };
+            let end = index + repeat as usize;
+            lengths.get_mut(index..end)?.fill(value);
+            index = end;
+        }
+
+        // There must be a code for the end of the block.
+        if lengths[256] == 0 {
+            return None;
+        }
+
+        let distances = Huffman::new(&lengths[nlen..])?;
+        let lengths = Huffman::new(&lengths[..nlen])?;
+        self.codes(&lengths, &distances)
+    }
+
+    fn run(&mut self) -> Option<()> {
+        loop {
+            let last = self.bits.bits(1)?;
+            match self.bits.bits(2)? {
+                0 => self.stored()?,
+                1 => self.fixed()?,
+                2 => self.dynamic()?,
+                _ => return None,
+            }
+
+            if last == 1 || self.full() {
+                return Some(());
+            }
+        }
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, up to `limit` bytes of output.
+fn inflate(input: &[u8], limit: usize) -> Decoded {
+    let mut inflater = Inflater {
+        bits: Bits::new(input),
+        out: Vec::new(),
+        limit,
+    };
+
+    let finished = inflater.run().is_some();
+    let complete = finished && !inflater.full();
+    inflater.out.truncate(limit);
+
+    Decoded {
+        bytes: inflater.out,
+        complete,
+    }
+}
+
+/// Skips the gzip header, returning the DEFLATE data after it.
+fn gzip_body(input: &[u8]) -> Option<&[u8]> {
+    let flags = *input.get(3)?;
+    let mut pos = 10;
+
+    if flags & GZIP_FEXTRA != 0 {
+        let len = u16::from_le_bytes([*input.get(pos)?, *input.get(pos + 1)?]);
+        pos += 2 + usize::from(len);
+    }
+    for flag in &[GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            pos += input.get(pos..)?.iter().position(|&b| b == 0)? + 1;
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        pos += 2;
+    }
+
+    input.get(pos..)
+}
+
+/// Decompresses the first member of a gzip file, up to `limit` bytes of output.
+pub(super) fn gunzip(input: &[u8], limit: usize) -> Option<Decoded> {
+    Some(inflate(gzip_body(input)?, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::gunzip;
+    use std::{fs, io};
+
+    #[test]
+    fn gzip() -> io::Result<()> {
+        let decoded = gunzip(&fs::read("test.gz")?, 1024).unwrap();
+        assert_eq!(decoded.bytes, b"hello\n");
+        assert!(decoded.complete);
+
+        let decoded = gunzip(&fs::read("test.gz")?, 3).unwrap();
+        assert_eq!(decoded.bytes, b"hel");
+        assert!(!decoded.complete);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+//! An LZ4 decoder for the frame format and the legacy format.
+
+use super::Decoded;
+use crate::{
+    bytes::u32_le,
+    compress::{self, LZ4_MAGIC},
+};
+
+const LEGACY_MAGIC: &[u8] = &[0x02, 0x21, 0x4c, 0x18];
+/// Legacy blocks all decompress to 8MiB, except perhaps the last.
+const LEGACY_BLOCK_LEN: usize = 8 << 20;
+
+const FLAG_BLOCK_CHECKSUM: u8 = 1 << 4;
+const FLAG_CONTENT_SIZE: u8 = 1 << 3;
+const FLAG_DICT_ID: u8 = 1 << 0;
+const VERSION_MASK: u8 = 0b1100_0000;
+const VERSION_1: u8 = 0b0100_0000;
+const UNCOMPRESSED_BLOCK: u32 = 1 << 31;
+
+/// Reads an LZ4 length: `nibble`, extended by bytes that are added on for as long as
+/// they're 255.
+fn length(input: &[u8], pos: &mut usize, nibble: u8) -> Option<usize> {
+    let mut len = usize::from(nibble);
+    if nibble == 15 {
+        loop {
+            let byte = *input.get(*pos)?;
+            *pos += 1;
+            len = len.checked_add(usize::from(byte))?;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
+
+/// Decompresses one block onto `out`, which holds any earlier blocks matches may refer to.
+fn block(input: &[u8], out: &mut Vec<u8>, limit: usize) -> Option<()> {
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let token = input[pos];
+        pos += 1;
+
+        let literals = length(input, &mut pos, token >> 4)?;
+        let data = input.get(pos..pos.checked_add(literals)?)?;
+        out.extend_from_slice(&data[..literals.min(limit.saturating_sub(out.len()))]);
+        pos += literals;
+
+        // The last sequence is only literals.
+        if pos == input.len() || out.len() >= limit {
+            return Some(());
+        }
+
+        let offset = usize::from(u16::from_le_bytes([*input.get(pos)?, *input.get(pos + 1)?]));
+        pos += 2;
+        let len = length(input, &mut pos, token & 0xf)? + 4;
+
+        let start = out.len().checked_sub(offset).filter(|_| offset > 0)?;
+        for i in 0..len.min(limit - out.len()) {
+            let byte = out[start + i];
+            out.push(byte);
+        }
+    }
+
+    Some(())
+}
+
+/// Decompresses blocks from `input` after the frame header, returning whether the end
+/// mark was reached.
+fn frame_blocks(input: &[u8], checksums: bool, out: &mut Vec<u8>, limit: usize) -> Option<()> {
+    let mut pos = 0;
+
+    loop {
+        if out.len() >= limit {
+            return None;
+        }
+
+        let size = u32_le(input, pos)?;
+        pos += 4;
+        if size == 0 {
+            return Some(());
+        }
+
+        let len = (size & !UNCOMPRESSED_BLOCK) as usize;
+        let data = input.get(pos..pos.checked_add(len)?)?;
+        if size & UNCOMPRESSED_BLOCK != 0 {
+            out.extend_from_slice(&data[..len.min(limit - out.len())]);
+        } else {
+            block(data, out, limit)?;
+        }
+
+        pos += len + if checksums { 4 } else { 0 };
+    }
+}
+
+fn frame(input: &[u8], out: &mut Vec<u8>, limit: usize) -> Option<()> {
+    let flags = *input.get(4)?;
+    if flags & VERSION_MASK != VERSION_1 {
+        return None;
+    }
+
+    // Magic, flags, block descriptor and header checksum, plus the optional fields.
+    let mut header = 7;
+    if flags & FLAG_CONTENT_SIZE != 0 {
+        header += 8;
+    }
+    if flags & FLAG_DICT_ID != 0 {
+        header += 4;
+    }
+
+    frame_blocks(
+        input.get(header..)?,
+        flags & FLAG_BLOCK_CHECKSUM != 0,
+        out,
+        limit,
+    )
+}
+
+fn legacy(input: &[u8], out: &mut Vec<u8>, limit: usize) -> Option<()> {
+    let mut pos = LEGACY_MAGIC.len();
+
+    while pos < input.len() {
+        if out.len() >= limit {
+            return None;
+        }
+
+        let len = u32_le(input, pos)? as usize;
+        pos += 4;
+        let data = input.get(pos..pos.checked_add(len)?)?;
+
+        let before = out.len();
+        block(data, out, limit)?;
+        // A short block is the last one; anything after it is another stream.
+        if out.len() - before < LEGACY_BLOCK_LEN {
+            return Some(());
+        }
+        pos += len;
+    }
+
+    Some(())
+}
+
+/// Decompresses the first LZ4 frame, after any skippable frames, up to `limit` bytes of
+/// output.
+pub(super) fn unlz4(input: &[u8], limit: usize) -> Option<Decoded> {
+    let input = compress::skip_frames(input)?;
+    let mut out = Vec::new();
+
+    let finished = if input.starts_with(LZ4_MAGIC) {
+        frame(input, &mut out, limit)
+    } else if input.starts_with(LEGACY_MAGIC) {
+        legacy(input, &mut out, limit)
+    } else {
+        return None;
+    };
+
+    let complete = finished.is_some() && out.len() < limit;
+    out.truncate(limit);
+
+    Some(Decoded {
+        bytes: out,
+        complete,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unlz4;
+    use std::{fs, io};
+
+    #[test]
+    fn frame() -> io::Result<()> {
+        let decoded = unlz4(&fs::read("test.lz4")?, 1024).unwrap();
+        assert_eq!(decoded.bytes, b"hello\n");
+        assert!(decoded.complete);
+
+        let tar = fs::read("test-ustar.tar")?;
+        let decoded = unlz4(&fs::read("test.tar.lz4")?, 1 << 20).unwrap();
+        assert_eq!(decoded.bytes, tar);
+        assert!(decoded.complete);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! Looking through compression to the format inside, by decompressing just the start of
+//! each layer.
+//!
+//! Gzip, bzip2 and LZ4 can be looked through. Xz and Zstandard can't yet, so they're always
+//! the innermost layer reported.
+//!
+//! The decoders are this crate's own rather than `flate2`, `bzip2` or `lz4_flex`, so that it
+//! keeps to no dependencies, and because detection only ever wants a prefix: each decoder
+//! stops at the byte budget, and the bzip2 one won't start a block longer than that. None
+//! of them verify checksums, which a prefix can't be checked against. Their tests cover
+//! truncated and corrupted streams, and `fuzz/` has a cargo-fuzz target for each.
+
+mod bzip2;
+mod inflate;
+mod lz4;
+
+use crate::{detect_sample, FileType, Sample};
+use std::borrow::Cow;
+
+/// Output of one of the decompressors.
+struct Decoded {
+    bytes: Vec<u8>,
+    /// Whether `bytes` is the whole payload, rather than a prefix of it.
+    complete: bool,
+}
+
+/// How far [`detect_layers`] goes into compressed input.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct LayerLimits {
+    /// Most compression layers to look through.
+    pub max_depth: usize,
+    /// Most bytes to decompress from each layer.
+    ///
+    /// Inner formats are detected from what fits in the budget, and trailers (like the
+    /// ZIP central directory) are only seen if the whole payload does. A bzip2 block has to
+    /// be decoded whole, so one longer than this ends that layer's output.
+    pub max_bytes: usize,
+}
+
+impl Default for LayerLimits {
+    fn default() -> Self {
+        LayerLimits {
+            max_depth: 4,
+            max_bytes: 1 << 20,
+        }
+    }
+}
+
+fn decompress(file_type: FileType, bytes: &[u8], limit: usize) -> Option<Decoded> {
+    match file_type {
+        FileType::Gzip => inflate::gunzip(bytes, limit),
+        FileType::Bzip2 => bzip2::bunzip(bytes, limit),
+        FileType::Lz4 => lz4::unlz4(bytes, limit),
+        _ => None,
+    }
+}
+
+/// Detects the type of `bytes` and, while that's a compression format, the type of what
+/// it decompresses to, outermost first.
+///
+/// A bzip2-compressed tarball gives `[Bzip2, Tar]`. Detection stops when a layer isn't
+/// recognised, can't be decompressed, or `limits` are reached, so the result is empty
+/// only if `bytes` itself isn't recognised.
+pub fn detect_layers(bytes: &[u8], limits: &LayerLimits) -> Vec<FileType> {
+    let mut layers = Vec::new();
+    let mut current = Cow::Borrowed(bytes);
+    let mut complete = true;
+
+    loop {
+        let sample = if complete {
+            Sample::new(&current)
+        } else {
+            // Without the end of the payload its length isn't known, which `tail: None`
+            // tells the validators.
+            Sample {
+                head: &current,
+                tail: None,
+                len: current.len() as u64,
+            }
+        };
+
+        let file_type = match detect_sample(&sample) {
+            Some(file_type) => file_type,
+            None => break,
+        };
+        layers.push(file_type);
+
+        if layers.len() > limits.max_depth {
+            break;
+        }
+
+        match decompress(file_type, &current, limits.max_bytes) {
+            Some(decoded) => {
+                complete = decoded.complete;
+                current = Cow::Owned(decoded.bytes);
+            }
+            None => break,
+        }
+    }
+
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bzip2, detect_layers, inflate, lz4, Decoded, LayerLimits};
+    use crate::FileType;
+    use std::{fs, io};
+
+    /// Checks `decode` copes with `path` cut short, which must decode to a prefix of
+    /// `payload`, and with each bit of it flipped, which mustn't overrun the limit.
+    fn check_malformed(
+        decode: fn(&[u8], usize) -> Option<Decoded>,
+        path: &str,
+        payload: &str,
+    ) -> io::Result<()> {
+        let payload = fs::read(payload)?;
+        let mut bytes = fs::read(path)?;
+
+        for len in 0..bytes.len() {
+            if let Some(decoded) = decode(&bytes[..len], 1 << 20) {
+                assert!(payload.starts_with(&decoded.bytes), "{} {}", path, len);
+                assert!(
+                    !decoded.complete || decoded.bytes == payload,
+                    "{} {}",
+                    path,
+                    len
+                );
+            }
+        }
+
+        for pos in 0..bytes.len() {
+            for bit in 0..8 {
+                bytes[pos] ^= 1 << bit;
+                if let Some(decoded) = decode(&bytes, 1000) {
+                    assert!(decoded.bytes.len() <= 1000, "{} {} {}", path, pos, bit);
+                }
+                bytes[pos] ^= 1 << bit;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn tarballs() -> io::Result<()> {
+        for (path, outer) in &[
+            ("test.tar.bz2", FileType::Bzip2),
+            ("test.tar.gz", FileType::Gzip),
+            ("test.tar.lz4", FileType::Lz4),
+        ] {
+            assert_eq!(
+                detect_layers(&fs::read(path)?, &LayerLimits::default()),
+                vec![*outer, FileType::Tar],
+                "{}",
+                path
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested() -> io::Result<()> {
+        let bytes = fs::read("test.tar.bz2.gz")?;
+
+        assert_eq!(
+            detect_layers(&bytes, &LayerLimits::default()),
+            vec![FileType::Gzip, FileType::Bzip2, FileType::Tar]
+        );
+        assert_eq!(
+            detect_layers(
+                &bytes,
+                &LayerLimits {
+                    max_depth: 1,
+                    ..LayerLimits::default()
+                }
+            ),
+            vec![FileType::Gzip, FileType::Bzip2]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn budget() -> io::Result<()> {
+        let limits = LayerLimits {
+            max_bytes: 100,
+            ..LayerLimits::default()
+        };
+
+        // The tar magic is further in than 100 bytes.
+        assert_eq!(
+            detect_layers(&fs::read("test.tar.gz")?, &limits),
+            vec![FileType::Gzip]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_layer() {
+        // A gzip member holding one stored block.
+        let gzip = |payload: &[u8]| {
+            let mut bytes = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff, 1];
+            bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&(!(payload.len() as u16)).to_le_bytes());
+            bytes.extend_from_slice(payload);
+            bytes
+        };
+
+        // Two ADTS frames, which are only AAC if nothing follows them.
+        let mut aac = Vec::new();
+        for _ in 0..2 {
+            aac.extend_from_slice(&[0xff, 0xf1, 0x50, 0x80, 50 >> 3, 50 << 5, 0]);
+            aac.resize(aac.len() + 43, 0);
+        }
+        assert_eq!(
+            detect_layers(&gzip(&aac), &LayerLimits::default()),
+            vec![FileType::Gzip, FileType::Aac]
+        );
+
+        // Cut short by the budget, the frames fill what was decompressed but not the payload.
+        aac.extend_from_slice(&[0; 100]);
+        let limits = LayerLimits {
+            max_bytes: 100,
+            ..LayerLimits::default()
+        };
+        assert_eq!(detect_layers(&gzip(&aac), &limits), vec![FileType::Gzip]);
+    }
+
+    #[test]
+    fn malformed() -> io::Result<()> {
+        check_malformed(bzip2::bunzip, "test.tar.bz2", "test-ustar.tar")?;
+        check_malformed(inflate::gunzip, "test.tar.gz", "test-ustar.tar")?;
+        check_malformed(lz4::unlz4, "test.tar.lz4", "test-ustar.tar")
+    }
+
+    #[test]
+    fn not_compressed() -> io::Result<()> {
+        let limits = LayerLimits::default();
+
+        assert_eq!(
+            detect_layers(&fs::read("test.png")?, &limits),
+            vec![FileType::Png]
+        );
+        assert_eq!(
+            detect_layers(&fs::read("test.bz2")?, &limits),
+            vec![FileType::Bzip2]
+        );
+        assert_eq!(detect_layers(b"plain text", &limits), vec![]);
+
+        Ok(())
+    }
+}
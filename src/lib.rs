@@ -1,3 +1,40 @@
+mod audio;
+mod bytecode;
+mod bytes;
+mod cfb;
+mod codec;
+mod compress;
+mod document;
+mod elf;
+mod font;
+mod image;
+mod isobmff;
+#[cfg(feature = "decompress")]
+mod layers;
+mod macho;
+mod mismatch;
+mod pe;
+mod rar;
+mod read;
+mod tar;
+mod video;
+mod zip;
+
+pub use bytecode::{Dex, JavaClass, Pyc, Wasm};
+pub use codec::{detect_codecs, mime_with_codecs, Codec, Track};
+pub use elf::{Elf, ElfClass, ElfKind, ElfMachine, Endianness};
+pub use isobmff::Ftyp;
+#[cfg(feature = "decompress")]
+pub use layers::{detect_layers, LayerLimits};
+pub use macho::{MachO, MachOCpu, MachOSlice};
+pub use mismatch::{check_extension, Verdict};
+pub use pe::{Pe, PeFormat, PeMachine, PeSubsystem};
+pub use rar::RarVersion;
+pub use read::{detect_from_reader, detect_from_seekable, detect_path, SpecialFile};
+pub use tar::TarFlavour;
+
+use std::cmp::Reverse;
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum FileType {
     // -- Images --
@@ -5,29 +42,609 @@ pub enum FileType {
     Jpeg,
     Png,
     Bmp,
+    Gif,
+    WebP,
+    /// TIFF, in either byte order, or BigTIFF.
+    Tiff,
+    /// Windows icon.
+    Ico,
+    /// Windows cursor, which is laid out like an icon.
+    Cur,
+    /// Photoshop document, including the large document (PSB) variant.
+    Psd,
+    /// JPEG 2000 image in the JP2 container.
+    Jp2,
+    /// Bare JPEG 2000 codestream.
+    J2k,
+    /// JPEG XL, either a bare codestream or in its container.
+    JpegXl,
+    /// Quite OK Image format.
+    Qoi,
+    /// HEIF image coded with HEVC, as phone cameras take.
+    Heic,
+    /// HEIF image in a coding without a more specific type.
+    Heif,
+    /// HEIF image coded with AV1.
+    Avif,
+
+    // -- Audio --
+    /// MPEG-4 audio, including audiobooks and protected iTunes audio.
+    M4a,
+    /// MPEG audio layer III, recognised by an ID3v2 tag or by a run of frames.
+    Mp3,
+    /// AAC in an ADTS stream.
+    Aac,
+    Flac,
+    /// Ogg container, whatever codec it holds.
+    Ogg,
+    Wav,
+    /// AIFF, including the compressed AIFF-C variant.
+    Aiff,
+    /// Standard MIDI file.
+    Midi,
+
+    // -- Video --
+    // ISO base media files are told apart by the brands in their `ftyp` box. See
+    // `Ftyp`.
+    Mp4,
+    /// Apple's variant of MP4, as iTunes sells video in.
+    M4v,
+    /// QuickTime movie.
+    Mov,
+    /// 3GPP multimedia, for GSM phones.
+    ThreeGp,
+    /// 3GPP2 multimedia, for CDMA phones.
+    ThreeG2,
+    /// Matroska, told apart from WebM by the DocType in its EBML header.
+    Matroska,
+    WebM,
+    Avi,
+    /// Flash video.
+    Flv,
+    /// MPEG program stream, as DVDs use.
+    MpegPs,
+    /// MPEG transport stream, including the M2TS variant Blu-ray and AVCHD use.
+    MpegTs,
+
+    // -- Documents --
+    Pdf,
+    PostScript,
+    /// Encapsulated PostScript, including DOS EPS binaries with a preview image.
+    Eps,
+    Rtf,
+    /// Compound File Binary, the "OLE2" container, where it isn't one of the more specific
+    /// types below.
+    Cfb,
+
+    // -- Fonts --
+    /// TrueType font, or an OpenType font with TrueType outlines.
+    TrueType,
+    /// OpenType font with CFF outlines.
+    OpenType,
+    /// TrueType or OpenType collection, holding several fonts that share tables.
+    FontCollection,
+    Woff,
+    Woff2,
+    /// PostScript Type 1 font, in either its ASCII (PFA) or binary (PFB) form.
+    Type1,
+
+    // -- Executables --
+    /// ELF executable, shared object, object file or core dump. See [`Elf`].
+    Elf,
+    /// Windows PE executable, DLL, driver or .NET assembly. See [`Pe`].
+    Pe,
+    /// DOS executable, with an `MZ` header that doesn't lead to PE headers.
+    DosExe,
+    /// Mach-O executable, library, bundle or object file, thin or universal. See
+    /// [`MachO`].
+    MachO,
+
+    // -- Bytecode --
+    /// WebAssembly binary module or component. See [`Wasm`].
+    Wasm,
+    /// Java class file. See [`JavaClass`].
+    JavaClass,
+    /// Android DEX file, or an optimised ODEX one. See [`Dex`].
+    Dex,
+    /// LLVM bitcode, raw or in the wrapper header Apple's toolchains add.
+    LlvmBitcode,
+    /// CPython's cached bytecode, from Python 3.6 to 3.13. See [`Pyc`].
+    Pyc,
 
     // -- Compression/Archives --
+    /// ZIP archive, including spanned and self-extracting archives and ones with data
+    /// prepended, which are found by their end-of-central-directory record.
     Zip,
     Bzip2,
-    /// Tar archive format.
+    /// Tar archive format, in any of the flavours [`TarFlavour`] lists.
     ///
-    /// For some reason empty tar files don't have this number, only tar files with at least
-    /// one element
+    /// Archives are recognised by the magic in their first header, or failing that by its
+    /// checksum. Empty archives have no header at all, only the two zero-filled
+    /// end-of-archive blocks, on their own or padded to a 10240-byte record.
     Tar,
+    Gzip,
+    Xz,
+    /// Zstandard, including streams that start with skippable frames.
+    Zstd,
+    /// LZ4 frame format, or the legacy format the `lz4` tool used to write.
+    Lz4,
+    SevenZip,
+    /// RAR archive, of any of the generations [`RarVersion`] lists.
+    Rar,
+
+    // -- ZIP-based containers --
+    // All of these are ZIP archives, told apart by the entries inside. See
+    // `FileType::container`.
+    /// Office Open XML word processing document.
+    Docx,
+    /// Office Open XML spreadsheet.
+    Xlsx,
+    /// Office Open XML presentation.
+    Pptx,
+    /// OpenDocument text.
+    Odt,
+    /// OpenDocument spreadsheet.
+    Ods,
+    /// OpenDocument presentation.
+    Odp,
+    /// OpenDocument drawing.
+    Odg,
+    Epub,
+    /// Java archive.
+    Jar,
+    /// Android application package.
+    Apk,
+
+    // -- CFB-based containers --
+    // All of these are compound files, told apart by the streams inside. See
+    // `FileType::container`.
+    /// Legacy Word document.
+    Doc,
+    /// Legacy Excel workbook.
+    Xls,
+    /// Legacy PowerPoint presentation.
+    Ppt,
+    /// Outlook message.
+    Msg,
 }
 
 impl FileType {
+    /// Every file type, in declaration order.
+    pub const ALL: &'static [FileType] = &[
+        FileType::Tga,
+        FileType::Jpeg,
+        FileType::Png,
+        FileType::Bmp,
+        FileType::Gif,
+        FileType::WebP,
+        FileType::Tiff,
+        FileType::Ico,
+        FileType::Cur,
+        FileType::Psd,
+        FileType::Jp2,
+        FileType::J2k,
+        FileType::JpegXl,
+        FileType::Qoi,
+        FileType::Heic,
+        FileType::Heif,
+        FileType::Avif,
+        FileType::M4a,
+        FileType::Mp3,
+        FileType::Aac,
+        FileType::Flac,
+        FileType::Ogg,
+        FileType::Wav,
+        FileType::Aiff,
+        FileType::Midi,
+        FileType::Mp4,
+        FileType::M4v,
+        FileType::Mov,
+        FileType::ThreeGp,
+        FileType::ThreeG2,
+        FileType::Matroska,
+        FileType::WebM,
+        FileType::Avi,
+        FileType::Flv,
+        FileType::MpegPs,
+        FileType::MpegTs,
+        FileType::Pdf,
+        FileType::PostScript,
+        FileType::Eps,
+        FileType::Rtf,
+        FileType::Cfb,
+        FileType::TrueType,
+        FileType::OpenType,
+        FileType::FontCollection,
+        FileType::Woff,
+        FileType::Woff2,
+        FileType::Type1,
+        FileType::Elf,
+        FileType::Pe,
+        FileType::DosExe,
+        FileType::MachO,
+        FileType::Wasm,
+        FileType::JavaClass,
+        FileType::Dex,
+        FileType::LlvmBitcode,
+        FileType::Pyc,
+        FileType::Zip,
+        FileType::Bzip2,
+        FileType::Tar,
+        FileType::Gzip,
+        FileType::Xz,
+        FileType::Zstd,
+        FileType::Lz4,
+        FileType::SevenZip,
+        FileType::Rar,
+        FileType::Docx,
+        FileType::Xlsx,
+        FileType::Pptx,
+        FileType::Odt,
+        FileType::Ods,
+        FileType::Odp,
+        FileType::Odg,
+        FileType::Epub,
+        FileType::Jar,
+        FileType::Apk,
+        FileType::Doc,
+        FileType::Xls,
+        FileType::Ppt,
+        FileType::Msg,
+    ];
+
+    /// The more general format this type is built on, if any.
+    ///
+    /// Every type detected by looking inside a ZIP archive, such as [`FileType::Docx`],
+    /// has [`FileType::Zip`] as its container, and likewise for compound files and
+    /// [`FileType::Cfb`].
+    pub fn container(&self) -> Option<FileType> {
+        match self {
+            FileType::Docx
+            | FileType::Xlsx
+            | FileType::Pptx
+            | FileType::Odt
+            | FileType::Ods
+            | FileType::Odp
+            | FileType::Odg
+            | FileType::Epub
+            | FileType::Jar
+            | FileType::Apk => Some(FileType::Zip),
+            FileType::Doc | FileType::Xls | FileType::Ppt | FileType::Msg => Some(FileType::Cfb),
+            _ => None,
+        }
+    }
+
+    /// The preferred extension, without a leading dot.
     pub fn extension(&self) -> &'static str {
+        self.extensions()[0]
+    }
+
+    /// All extensions commonly used for this type, preferred one first.
+    ///
+    /// A few extensions are shared, such as `exe` by Windows and DOS executables. Those
+    /// belong to the first type in [`FileType::ALL`] as far as
+    /// [`FileType::from_extension`] is concerned.
+    ///
+    /// Shorthands for compressed tarballs such as `tbz2` belong to the compression format,
+    /// since that's what their content is detected as.
+    pub fn extensions(&self) -> &'static [&'static str] {
         match self {
-            FileType::Tga => "tga",
-            FileType::Jpeg => "jpg",
-            FileType::Png => "png",
-            FileType::Bmp => "bmp",
-            FileType::Zip => "zip",
-            FileType::Bzip2 => "bz2",
-            FileType::Tar => "tar",
+            FileType::Tga => &["tga", "icb", "vda", "vst"],
+            FileType::Jpeg => &["jpg", "jpeg", "jpe", "jfif"],
+            FileType::Png => &["png"],
+            FileType::Bmp => &["bmp", "dib"],
+            FileType::Gif => &["gif"],
+            FileType::WebP => &["webp"],
+            FileType::Tiff => &["tif", "tiff"],
+            FileType::Ico => &["ico"],
+            FileType::Cur => &["cur"],
+            FileType::Psd => &["psd", "psb"],
+            FileType::Jp2 => &["jp2", "jpf", "jpx"],
+            FileType::J2k => &["j2k", "j2c", "jpc"],
+            FileType::JpegXl => &["jxl"],
+            FileType::Qoi => &["qoi"],
+            FileType::Heic => &["heic", "heics"],
+            FileType::Heif => &["heif", "heifs", "hif"],
+            FileType::Avif => &["avif", "avifs"],
+            FileType::M4a => &["m4a", "m4b", "m4p"],
+            FileType::Mp3 => &["mp3"],
+            FileType::Aac => &["aac", "adts"],
+            FileType::Flac => &["flac"],
+            FileType::Ogg => &["ogg", "oga", "ogv", "ogx", "opus", "spx"],
+            FileType::Wav => &["wav", "wave"],
+            FileType::Aiff => &["aiff", "aif", "aifc"],
+            FileType::Midi => &["mid", "midi", "smf", "kar"],
+            FileType::Mp4 => &["mp4", "f4v"],
+            FileType::M4v => &["m4v"],
+            FileType::Mov => &["mov", "qt"],
+            FileType::ThreeGp => &["3gp", "3gpp"],
+            FileType::ThreeG2 => &["3g2", "3gpp2"],
+            FileType::Matroska => &["mkv", "mka", "mks", "mk3d"],
+            FileType::WebM => &["webm"],
+            FileType::Avi => &["avi"],
+            FileType::Flv => &["flv"],
+            FileType::MpegPs => &["mpg", "mpeg", "mpe", "vob", "m2p"],
+            FileType::MpegTs => &["ts", "m2ts", "mts", "m2t", "tsv"],
+            FileType::Pdf => &["pdf"],
+            FileType::PostScript => &["ps"],
+            FileType::Eps => &["eps", "epsf", "epsi"],
+            FileType::Rtf => &["rtf"],
+            FileType::Cfb => &["cfb"],
+            FileType::TrueType => &["ttf"],
+            FileType::OpenType => &["otf"],
+            FileType::FontCollection => &["ttc", "otc"],
+            FileType::Woff => &["woff"],
+            FileType::Woff2 => &["woff2"],
+            FileType::Type1 => &["pfb", "pfa", "t1"],
+            FileType::Elf => &["elf", "so", "o", "ko"],
+            FileType::Pe => &[
+                "exe", "dll", "sys", "efi", "scr", "ocx", "cpl", "drv", "mui",
+            ],
+            FileType::DosExe => &["exe", "ovl"],
+            FileType::MachO => &["macho", "dylib", "bundle"],
+            FileType::Wasm => &["wasm"],
+            FileType::JavaClass => &["class"],
+            FileType::Dex => &["dex", "odex"],
+            FileType::LlvmBitcode => &["bc"],
+            FileType::Pyc => &["pyc", "pyo"],
+            FileType::Zip => &["zip"],
+            FileType::Bzip2 => &["bz2", "bz", "tbz2", "tbz"],
+            FileType::Tar => &["tar"],
+            FileType::Gzip => &["gz", "tgz"],
+            FileType::Xz => &["xz", "txz"],
+            FileType::Zstd => &["zst", "tzst"],
+            FileType::Lz4 => &["lz4"],
+            FileType::SevenZip => &["7z"],
+            FileType::Rar => &["rar"],
+            FileType::Docx => &["docx", "docm", "dotx", "dotm"],
+            FileType::Xlsx => &["xlsx", "xlsm", "xltx", "xltm"],
+            FileType::Pptx => &["pptx", "pptm", "potx", "potm", "ppsx", "ppsm"],
+            FileType::Odt => &["odt", "ott"],
+            FileType::Ods => &["ods", "ots"],
+            FileType::Odp => &["odp", "otp"],
+            FileType::Odg => &["odg", "otg"],
+            FileType::Epub => &["epub"],
+            FileType::Jar => &["jar", "war", "ear"],
+            FileType::Apk => &["apk"],
+            FileType::Doc => &["doc", "dot"],
+            FileType::Xls => &["xls", "xlt"],
+            FileType::Ppt => &["ppt", "pot", "pps"],
+            FileType::Msg => &["msg"],
         }
     }
+
+    /// Looks up the type that uses `extension`.
+    ///
+    /// Matching is case-insensitive and a single leading dot is ignored, so `"JPEG"` and
+    /// `".jpeg"` both give [`FileType::Jpeg`].
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+        FileType::ALL.iter().copied().find(|ty| {
+            ty.extensions()
+                .iter()
+                .any(|ext| ext.eq_ignore_ascii_case(extension))
+        })
+    }
+
+    /// The IANA media type, suitable for a `Content-Type` header.
+    ///
+    /// Types without a registered media type use their common `x-` name.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            FileType::Tga => "image/x-tga",
+            FileType::Jpeg => "image/jpeg",
+            FileType::Png => "image/png",
+            FileType::Bmp => "image/bmp",
+            FileType::Gif => "image/gif",
+            FileType::WebP => "image/webp",
+            FileType::Tiff => "image/tiff",
+            FileType::Ico => "image/vnd.microsoft.icon",
+            FileType::Cur => "image/x-win-bitmap",
+            FileType::Psd => "image/vnd.adobe.photoshop",
+            FileType::Jp2 => "image/jp2",
+            FileType::J2k => "image/x-jp2-codestream",
+            FileType::JpegXl => "image/jxl",
+            FileType::Qoi => "image/qoi",
+            FileType::Heic => "image/heic",
+            FileType::Heif => "image/heif",
+            FileType::Avif => "image/avif",
+            FileType::M4a => "audio/mp4",
+            FileType::Mp3 => "audio/mpeg",
+            FileType::Aac => "audio/aac",
+            FileType::Flac => "audio/flac",
+            FileType::Ogg => "audio/ogg",
+            FileType::Wav => "audio/wav",
+            FileType::Aiff => "audio/aiff",
+            FileType::Midi => "audio/midi",
+            FileType::Mp4 => "video/mp4",
+            FileType::M4v => "video/x-m4v",
+            FileType::Mov => "video/quicktime",
+            FileType::ThreeGp => "video/3gpp",
+            FileType::ThreeG2 => "video/3gpp2",
+            FileType::Matroska => "video/x-matroska",
+            FileType::WebM => "video/webm",
+            FileType::Avi => "video/x-msvideo",
+            FileType::Flv => "video/x-flv",
+            FileType::MpegPs => "video/mpeg",
+            FileType::MpegTs => "video/mp2t",
+            FileType::Pdf => "application/pdf",
+            FileType::PostScript => "application/postscript",
+            FileType::Eps => "image/x-eps",
+            FileType::Rtf => "application/rtf",
+            FileType::Cfb => "application/x-ole-storage",
+            FileType::TrueType => "font/ttf",
+            FileType::OpenType => "font/otf",
+            FileType::FontCollection => "font/collection",
+            FileType::Woff => "font/woff",
+            FileType::Woff2 => "font/woff2",
+            FileType::Type1 => "application/x-font-type1",
+            FileType::Elf => "application/x-elf",
+            FileType::Pe => "application/vnd.microsoft.portable-executable",
+            FileType::DosExe => "application/x-dosexec",
+            FileType::MachO => "application/x-mach-binary",
+            FileType::Wasm => "application/wasm",
+            FileType::JavaClass => "application/java-vm",
+            FileType::Dex => "application/vnd.android.dex",
+            FileType::LlvmBitcode => "application/x-llvm-bitcode",
+            FileType::Pyc => "application/x-python-bytecode",
+            FileType::Zip => "application/zip",
+            FileType::Bzip2 => "application/x-bzip2",
+            FileType::Tar => "application/x-tar",
+            FileType::Gzip => "application/gzip",
+            FileType::Xz => "application/x-xz",
+            FileType::Zstd => "application/zstd",
+            FileType::Lz4 => "application/x-lz4",
+            FileType::SevenZip => "application/x-7z-compressed",
+            FileType::Rar => "application/vnd.rar",
+            FileType::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            FileType::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            FileType::Pptx => {
+                "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+            }
+            FileType::Odt => "application/vnd.oasis.opendocument.text",
+            FileType::Ods => "application/vnd.oasis.opendocument.spreadsheet",
+            FileType::Odp => "application/vnd.oasis.opendocument.presentation",
+            FileType::Odg => "application/vnd.oasis.opendocument.graphics",
+            FileType::Epub => "application/epub+zip",
+            FileType::Jar => "application/java-archive",
+            FileType::Apk => "application/vnd.android.package-archive",
+            FileType::Doc => "application/msword",
+            FileType::Xls => "application/vnd.ms-excel",
+            FileType::Ppt => "application/vnd.ms-powerpoint",
+            FileType::Msg => "application/vnd.ms-outlook",
+        }
+    }
+
+    /// Looks up the type for a media type, accepting common aliases as well as the one
+    /// [`FileType::mime_type`] returns.
+    ///
+    /// Matching is case-insensitive and ignores parameters such as `; charset=binary`.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let essence = mime.split(';').next().unwrap_or("").trim();
+
+        Some(match essence.to_ascii_lowercase().as_str() {
+            "image/x-tga" | "image/x-targa" | "image/tga" | "image/targa" => FileType::Tga,
+            "image/jpeg" | "image/jpg" | "image/pjpeg" => FileType::Jpeg,
+            "image/png" | "image/x-png" => FileType::Png,
+            "image/bmp" | "image/x-bmp" | "image/x-ms-bmp" => FileType::Bmp,
+            "image/gif" => FileType::Gif,
+            "image/webp" => FileType::WebP,
+            "image/tiff" | "image/tiff-fx" => FileType::Tiff,
+            "image/vnd.microsoft.icon" | "image/x-icon" | "image/ico" => FileType::Ico,
+            "image/x-win-bitmap" => FileType::Cur,
+            "image/vnd.adobe.photoshop" | "image/x-photoshop" | "application/x-photoshop" => {
+                FileType::Psd
+            }
+            "image/jp2" | "image/jpx" => FileType::Jp2,
+            "image/x-jp2-codestream" | "image/j2c" => FileType::J2k,
+            "image/jxl" => FileType::JpegXl,
+            "image/qoi" | "image/x-qoi" => FileType::Qoi,
+            "image/heic" | "image/heic-sequence" => FileType::Heic,
+            "image/heif" | "image/heif-sequence" => FileType::Heif,
+            "image/avif" | "image/avif-sequence" => FileType::Avif,
+            "audio/mp4" | "audio/x-m4a" | "audio/m4a" | "audio/x-m4b" => FileType::M4a,
+            "audio/mpeg" | "audio/mp3" | "audio/x-mp3" | "audio/mpeg3" => FileType::Mp3,
+            "audio/aac" | "audio/x-aac" | "audio/aacp" | "audio/vnd.dlna.adts" => FileType::Aac,
+            "audio/flac" | "audio/x-flac" => FileType::Flac,
+            "audio/ogg" | "application/ogg" | "video/ogg" | "audio/x-ogg" => FileType::Ogg,
+            "audio/wav" | "audio/x-wav" | "audio/wave" | "audio/vnd.wave" => FileType::Wav,
+            "audio/aiff" | "audio/x-aiff" => FileType::Aiff,
+            "audio/midi" | "audio/x-midi" | "audio/mid" => FileType::Midi,
+            "video/mp4" | "application/mp4" | "video/x-f4v" => FileType::Mp4,
+            "video/x-m4v" => FileType::M4v,
+            "video/quicktime" => FileType::Mov,
+            "video/3gpp" | "audio/3gpp" => FileType::ThreeGp,
+            "video/3gpp2" | "audio/3gpp2" => FileType::ThreeG2,
+            "video/x-matroska" | "audio/x-matroska" | "video/matroska" => FileType::Matroska,
+            "video/webm" | "audio/webm" => FileType::WebM,
+            "video/x-msvideo" | "video/avi" | "video/msvideo" => FileType::Avi,
+            "video/x-flv" | "video/flv" => FileType::Flv,
+            "video/mpeg" | "video/mp2p" | "video/x-mpeg" => FileType::MpegPs,
+            "video/mp2t" | "video/vnd.dlna.mpeg-tts" => FileType::MpegTs,
+            "application/pdf" | "application/x-pdf" => FileType::Pdf,
+            "application/postscript" => FileType::PostScript,
+            "image/x-eps" | "image/eps" | "application/eps" | "application/x-eps" => FileType::Eps,
+            "application/rtf" | "text/rtf" => FileType::Rtf,
+            "application/x-ole-storage" | "application/x-cfb" => FileType::Cfb,
+            "font/ttf" | "font/sfnt" | "application/x-font-ttf" | "application/font-sfnt" => {
+                FileType::TrueType
+            }
+            "font/otf" | "application/x-font-otf" | "application/vnd.ms-opentype" => {
+                FileType::OpenType
+            }
+            "font/collection" | "application/x-font-ttc" => FileType::FontCollection,
+            "font/woff" | "application/font-woff" | "application/x-font-woff" => FileType::Woff,
+            "font/woff2" | "application/font-woff2" => FileType::Woff2,
+            "application/x-font-type1" | "application/x-font-pfb" | "application/x-font-pfa" => {
+                FileType::Type1
+            }
+            "application/x-elf"
+            | "application/x-executable"
+            | "application/x-sharedlib"
+            | "application/x-object"
+            | "application/x-coredump" => FileType::Elf,
+            "application/vnd.microsoft.portable-executable"
+            | "application/x-msdownload"
+            | "application/x-ms-dos-executable" => FileType::Pe,
+            "application/x-dosexec" => FileType::DosExe,
+            "application/x-mach-binary" | "application/x-mach-o" => FileType::MachO,
+            "application/wasm" => FileType::Wasm,
+            "application/java-vm" | "application/x-java-class" | "application/x-java-vm" => {
+                FileType::JavaClass
+            }
+            "application/vnd.android.dex" | "application/x-dex" => FileType::Dex,
+            "application/x-llvm-bitcode" | "application/x-llvm-bc" => FileType::LlvmBitcode,
+            "application/x-python-bytecode" | "application/x-python-code" => FileType::Pyc,
+            "application/zip" | "application/x-zip-compressed" | "application/x-zip" => {
+                FileType::Zip
+            }
+            "application/x-bzip2" | "application/x-bzip" => FileType::Bzip2,
+            "application/x-tar" | "application/x-gtar" | "application/x-ustar" => FileType::Tar,
+            "application/gzip" | "application/x-gzip" => FileType::Gzip,
+            "application/x-xz" => FileType::Xz,
+            "application/zstd" | "application/x-zstd" => FileType::Zstd,
+            "application/x-lz4" => FileType::Lz4,
+            "application/x-7z-compressed" => FileType::SevenZip,
+            "application/vnd.rar" | "application/x-rar-compressed" | "application/x-rar" => {
+                FileType::Rar
+            }
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            | "application/vnd.openxmlformats-officedocument.wordprocessingml.template" => {
+                FileType::Docx
+            }
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            | "application/vnd.openxmlformats-officedocument.spreadsheetml.template" => {
+                FileType::Xlsx
+            }
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+            | "application/vnd.openxmlformats-officedocument.presentationml.template"
+            | "application/vnd.openxmlformats-officedocument.presentationml.slideshow" => {
+                FileType::Pptx
+            }
+            "application/vnd.oasis.opendocument.text"
+            | "application/vnd.oasis.opendocument.text-template"
+            | "application/vnd.oasis.opendocument.text-master" => FileType::Odt,
+            "application/vnd.oasis.opendocument.spreadsheet"
+            | "application/vnd.oasis.opendocument.spreadsheet-template" => FileType::Ods,
+            "application/vnd.oasis.opendocument.presentation"
+            | "application/vnd.oasis.opendocument.presentation-template" => FileType::Odp,
+            "application/vnd.oasis.opendocument.graphics"
+            | "application/vnd.oasis.opendocument.graphics-template" => FileType::Odg,
+            "application/epub+zip" => FileType::Epub,
+            "application/java-archive" | "application/x-java-archive" | "application/jar" => {
+                FileType::Jar
+            }
+            "application/vnd.android.package-archive" => FileType::Apk,
+            "application/msword" => FileType::Doc,
+            "application/vnd.ms-excel" | "application/msexcel" => FileType::Xls,
+            "application/vnd.ms-powerpoint" | "application/mspowerpoint" => FileType::Ppt,
+            "application/vnd.ms-outlook" => FileType::Msg,
+            _ => return None,
+        })
+    }
 }
 
 struct Check {
@@ -52,6 +669,24 @@ impl Check {
     const fn new_with_offset(offset: usize, bytes: &'static [u8]) -> Self {
         Check { bytes, offset }
     }
+
+    /// Number of bytes the input must have for this check to be evaluated at all.
+    const fn len(&self) -> usize {
+        self.offset + self.bytes.len()
+    }
+
+    fn matches_start(&self, bytes: &[u8]) -> bool {
+        bytes
+            .get(self.offset..)
+            .is_some_and(|rest| rest.starts_with(self.bytes))
+    }
+
+    fn matches_end(&self, bytes: &[u8]) -> bool {
+        bytes
+            .len()
+            .checked_sub(self.offset)
+            .is_some_and(|end| bytes[..end].ends_with(self.bytes))
+    }
 }
 
 impl Default for Check {
@@ -60,9 +695,22 @@ impl Default for Check {
     }
 }
 
+/// Structural check run once an input's magic bytes have matched.
+///
+/// Returns `None` to reject the input outright, otherwise whether the structure confirmed
+/// the match.
+type Validator = fn(&Sample<'_>) -> Option<Strength>;
+
 struct Magic {
     start: Check,
     end: Check,
+    /// A second check from the start, for formats with magic in two places.
+    also: Check,
+    validate: Option<Validator>,
+    /// Bytes `validate` reads from the start of the input, on top of `start`.
+    head: usize,
+    /// Bytes `validate` reads from the end of the input, on top of `end`.
+    tail: usize,
 }
 
 impl Magic {
@@ -70,6 +718,10 @@ impl Magic {
         Magic {
             start: Check::new(bytes),
             end: Check::default(),
+            also: Check::default(),
+            validate: None,
+            head: 0,
+            tail: 0,
         }
     }
 
@@ -77,6 +729,10 @@ impl Magic {
         Magic {
             start: Check::new_with_offset(offset, bytes),
             end: Check::default(),
+            also: Check::default(),
+            validate: None,
+            head: 0,
+            tail: 0,
         }
     }
 
@@ -84,49 +740,639 @@ impl Magic {
         Magic {
             start: Check::default(),
             end: Check { bytes, offset: 0 },
+            also: Check::default(),
+            validate: None,
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// A signature with no fixed bytes at all, where `validate` alone decides.
+    const fn probe(validate: Validator) -> Self {
+        Magic {
+            start: Check::default(),
+            end: Check::default(),
+            also: Check::default(),
+            validate: Some(validate),
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    const fn and_at(self, offset: usize, bytes: &'static [u8]) -> Self {
+        Magic {
+            also: Check::new_with_offset(offset, bytes),
+            ..self
+        }
+    }
+
+    const fn validated(self, validate: Validator) -> Self {
+        Magic {
+            validate: Some(validate),
+            ..self
+        }
+    }
+
+    /// Declares how far into each end of the input the validator looks, so streams read
+    /// enough of it.
+    const fn reading(self, head: usize, tail: usize) -> Self {
+        Magic { head, tail, ..self }
+    }
+
+    const fn head_len(&self) -> usize {
+        let checks = if self.also.len() > self.start.len() {
+            self.also.len()
+        } else {
+            self.start.len()
+        };
+
+        if self.head > checks {
+            self.head
+        } else {
+            checks
+        }
+    }
+
+    const fn tail_len(&self) -> usize {
+        if self.tail > self.end.len() {
+            self.tail
+        } else {
+            self.end.len()
+        }
+    }
+
+    /// Whether `sample` satisfies all the checks.
+    ///
+    /// The start and end checks must not overlap, so inputs too short to hold both are
+    /// rejected rather than matched against a shared region. If the end of the input wasn't
+    /// read only magic without an end check can match.
+    fn matches(&self, sample: &Sample<'_>) -> bool {
+        if !self.start.matches_start(sample.head) || !self.also.matches_start(sample.head) {
+            return false;
+        }
+
+        match sample.tail {
+            Some(tail) => {
+                sample.len >= (self.start.len().max(self.also.len()) + self.end.len()) as u64
+                    && self.end.matches_end(tail)
+            }
+            None => self.end.len() == 0,
+        }
+    }
+
+    /// Checks `sample` against the signature and, if it matches, validates its structure.
+    fn evaluate(&self, sample: &Sample<'_>, file_type: FileType) -> Option<Match> {
+        if !self.matches(sample) {
+            return None;
+        }
+
+        let signature = self.start.bytes.len() + self.also.bytes.len() + self.end.bytes.len();
+        let structure = match self.validate {
+            Some(validate) => validate(sample)? == Strength::Strong,
+            None => false,
+        };
+
+        let strength = if structure || (self.validate.is_none() && signature >= 4) {
+            Strength::Strong
+        } else {
+            Strength::Weak
+        };
+
+        let bonus = if structure { 50 } else { 0 };
+
+        Some(Match {
+            file_type: refine(file_type, sample),
+            confidence: (signature * 8 + bonus).min(100) as u8,
+            strength,
+            checks: Checks {
+                start: !self.start.bytes.is_empty() || !self.also.bytes.is_empty(),
+                end: !self.end.bytes.is_empty(),
+                structure,
+            },
+        })
+    }
+}
+
+/// Narrows a matched container format down to the more specific type it holds, if it can
+/// tell.
+fn refine(file_type: FileType, sample: &Sample<'_>) -> FileType {
+    match file_type {
+        FileType::Zip => zip::subtype(sample).unwrap_or(file_type),
+        FileType::Mp4 => isobmff::subtype(sample).unwrap_or(file_type),
+        FileType::Mp3 => audio::id3_subtype(sample).unwrap_or(file_type),
+        FileType::Cfb => cfb::subtype(sample).unwrap_or(file_type),
+        _ => file_type,
+    }
+}
+
+/// A candidate type for an input, as returned by [`detect_all`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Match {
+    pub file_type: FileType,
+    /// How likely the match is to be right, from 0 to 100.
+    ///
+    /// This is one point per bit of signature that matched, so a two-byte magic number
+    /// scores 16 and anything with 13 or more bytes of signature scores 100. Passing
+    /// structural validation adds another 50.
+    pub confidence: u8,
+    /// Whether the match can be trusted on its own.
+    pub strength: Strength,
+    /// Which parts of the signature matched.
+    pub checks: Checks,
+}
+
+impl Match {
+    /// How [`detect_all`] orders matches: a strong match outranks a weak one whatever their
+    /// confidence.
+    fn rank(&self) -> (Strength, u8) {
+        (self.strength, self.confidence)
+    }
+}
+
+/// How much a [`Match`] can be trusted.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum Strength {
+    /// A short magic number that plenty of unrelated data starts with, and which deeper
+    /// validation couldn't confirm (or which the type has no validation for).
+    Weak,
+    /// A long signature, or one whose surrounding structure was validated.
+    Strong,
+}
+
+/// The parts of a signature that matched an input.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash, Default)]
+pub struct Checks {
+    /// Bytes at a fixed offset from the start of the input.
+    pub start: bool,
+    /// Bytes at a fixed offset from the end of the input.
+    pub end: bool,
+    /// Structural validation beyond the magic bytes, such as header fields that must agree
+    /// with the input's length.
+    pub structure: bool,
+}
+
+/// The parts of an input that `MAGIC_MAP` is evaluated against.
+///
+/// For an in-memory buffer both `head` and `tail` are the whole buffer. Streams only read
+/// the `HEAD_LEN` and `TAIL_LEN` windows, and `tail` is `None` when the end of the stream
+/// couldn't be reached.
+struct Sample<'a> {
+    head: &'a [u8],
+    tail: Option<&'a [u8]>,
+    /// Length of the whole input. Only known when `tail` is `Some`; otherwise it's `head`'s.
+    len: u64,
+}
+
+impl<'a> Sample<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Sample {
+            head: bytes,
+            tail: Some(bytes),
+            len: bytes.len() as u64,
         }
     }
 }
 
 const MAGIC_MAP: &[(Magic, FileType)] = &[
     (Magic::ends_with(b"TRUEVISION-XFILE.\0"), FileType::Tga),
-    (Magic::starts_with(&[0xff, 0xd8]), FileType::Jpeg),
+    (
+        Magic::starts_with(&[0xff, 0xd8]).validated(image::validate_jpeg),
+        FileType::Jpeg,
+    ),
     (
         Magic {
             start: Check::new(&[0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a]),
             end: Check::new(&[0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82]),
+            also: Check::default(),
+            validate: None,
+            head: 0,
+            tail: 0,
         },
         FileType::Png,
     ),
-    (Magic::starts_with(b"BM"), FileType::Bmp),
+    (
+        Magic::starts_with(b"BM").validated(image::validate_bmp),
+        FileType::Bmp,
+    ),
+    (Magic::starts_with(b"GIF87a"), FileType::Gif),
+    (Magic::starts_with(b"GIF89a"), FileType::Gif),
+    (
+        Magic::starts_with(b"RIFF").and_at(8, b"WEBP"),
+        FileType::WebP,
+    ),
+    (Magic::starts_with(b"II*\0"), FileType::Tiff),
+    (Magic::starts_with(b"MM\0*"), FileType::Tiff),
+    (Magic::starts_with(b"II+\0"), FileType::Tiff),
+    (Magic::starts_with(b"MM\0+"), FileType::Tiff),
+    (
+        Magic::starts_with(&[0, 0, 1, 0])
+            .validated(image::validate_ico)
+            .reading(image::ICON_HEAD_LEN, 0),
+        FileType::Ico,
+    ),
+    (
+        Magic::starts_with(&[0, 0, 2, 0])
+            .validated(image::validate_cur)
+            .reading(image::ICON_HEAD_LEN, 0),
+        FileType::Cur,
+    ),
+    (Magic::starts_with(b"8BPS\0\x01"), FileType::Psd),
+    (Magic::starts_with(b"8BPS\0\x02"), FileType::Psd),
+    (
+        Magic::starts_with(b"\0\0\0\x0cjP  \r\n\x87\n"),
+        FileType::Jp2,
+    ),
+    (Magic::starts_with(&[0xff, 0x4f, 0xff, 0x51]), FileType::J2k),
+    (
+        Magic::starts_with(b"\0\0\0\x0cJXL \r\n\x87\n"),
+        FileType::JpegXl,
+    ),
+    (Magic::starts_with(&[0xff, 0x0a]), FileType::JpegXl),
+    (
+        Magic::starts_with(b"qoif").validated(image::validate_qoi),
+        FileType::Qoi,
+    ),
+    // The brands decide between MP4, HEIF and the rest; see `refine`.
+    (
+        Magic::starts_with_offset(4, b"ftyp")
+            .validated(isobmff::validate_ftyp)
+            .reading(isobmff::HEAD_LEN, 0),
+        FileType::Mp4,
+    ),
+    // QuickTime movies from before `ftyp` existed start straight with the movie box.
+    (Magic::starts_with_offset(4, b"moov"), FileType::Mov),
+    (
+        Magic::starts_with(b"RIFF").and_at(8, b"WAVE"),
+        FileType::Wav,
+    ),
+    (
+        Magic::starts_with(b"FORM").and_at(8, b"AIFF"),
+        FileType::Aiff,
+    ),
+    (
+        Magic::starts_with(b"FORM").and_at(8, b"AIFC"),
+        FileType::Aiff,
+    ),
+    (Magic::starts_with(b"fLaC"), FileType::Flac),
+    (Magic::starts_with(b"OggS\0"), FileType::Ogg),
+    (Magic::starts_with(b"MThd\0\0\0\x06"), FileType::Midi),
+    // Tagged FLAC and AAC are told apart from MP3 by what follows the tag; see `refine`.
+    (
+        Magic::starts_with(b"ID3")
+            .validated(audio::validate_id3)
+            .reading(audio::FRAMES_HEAD_LEN, 0),
+        FileType::Mp3,
+    ),
+    (
+        Magic::starts_with(&[0xff])
+            .validated(audio::validate_mpeg_audio)
+            .reading(audio::FRAMES_HEAD_LEN, 0),
+        FileType::Mp3,
+    ),
+    (
+        Magic::starts_with(&[0xff])
+            .validated(audio::validate_adts)
+            .reading(audio::FRAMES_HEAD_LEN, 0),
+        FileType::Aac,
+    ),
+    (
+        Magic::starts_with(video::EBML_MAGIC)
+            .validated(video::validate_matroska)
+            .reading(video::EBML_HEAD_LEN, 0),
+        FileType::Matroska,
+    ),
+    (
+        Magic::starts_with(video::EBML_MAGIC)
+            .validated(video::validate_webm)
+            .reading(video::EBML_HEAD_LEN, 0),
+        FileType::WebM,
+    ),
+    (
+        Magic::starts_with(b"RIFF").and_at(8, b"AVI "),
+        FileType::Avi,
+    ),
+    (Magic::starts_with(b"FLV\x01"), FileType::Flv),
+    (
+        Magic::starts_with(&[0x00, 0x00, 0x01, 0xba]).validated(video::validate_program_stream),
+        FileType::MpegPs,
+    ),
+    (
+        Magic::starts_with(&[0x47])
+            .validated(video::validate_transport_stream)
+            .reading(video::TS_HEAD_LEN, 0),
+        FileType::MpegTs,
+    ),
+    (
+        Magic::starts_with_offset(4, &[0x47])
+            .validated(video::validate_m2ts)
+            .reading(video::TS_HEAD_LEN, 0),
+        FileType::MpegTs,
+    ),
+    (
+        Magic::starts_with(font::TRUETYPE_MAGIC)
+            .validated(font::validate_sfnt)
+            .reading(font::HEAD_LEN, 0),
+        FileType::TrueType,
+    ),
+    (
+        Magic::starts_with(font::APPLE_TRUETYPE_MAGIC)
+            .validated(font::validate_sfnt)
+            .reading(font::HEAD_LEN, 0),
+        FileType::TrueType,
+    ),
+    (
+        Magic::starts_with(font::OPENTYPE_MAGIC)
+            .validated(font::validate_sfnt)
+            .reading(font::HEAD_LEN, 0),
+        FileType::OpenType,
+    ),
+    (
+        Magic::starts_with(font::COLLECTION_MAGIC)
+            .validated(font::validate_collection)
+            .reading(font::HEAD_LEN, 0),
+        FileType::FontCollection,
+    ),
+    (
+        Magic::starts_with(font::WOFF_MAGIC).validated(font::validate_woff),
+        FileType::Woff,
+    ),
+    (
+        Magic::starts_with(font::WOFF2_MAGIC).validated(font::validate_woff2),
+        FileType::Woff2,
+    ),
+    // Before PostScript, which these first lines would otherwise pass for.
+    (Magic::starts_with(font::PFA_MAGIC), FileType::Type1),
+    (
+        Magic::starts_with(font::PFA_FONTTYPE_MAGIC),
+        FileType::Type1,
+    ),
+    (
+        Magic::starts_with(font::PFB_MAGIC).validated(font::validate_pfb),
+        FileType::Type1,
+    ),
+    (
+        Magic::starts_with(b"%PDF-").validated(document::validate_pdf),
+        FileType::Pdf,
+    ),
+    (
+        Magic::starts_with(b"%!PS-Adobe-")
+            .validated(document::validate_eps)
+            .reading(document::EPS_LINE_LEN, 0),
+        FileType::Eps,
+    ),
+    (Magic::starts_with(b"%!PS"), FileType::PostScript),
+    (
+        Magic::starts_with(&[0xc5, 0xd0, 0xd3, 0xc6])
+            .validated(document::validate_dos_eps)
+            .reading(document::DOS_EPS_HEAD_LEN, 0),
+        FileType::Eps,
+    ),
+    (Magic::starts_with(b"{\\rtf"), FileType::Rtf),
+    // The streams inside decide between the Office formats; see `refine`.
+    (
+        Magic::starts_with(cfb::MAGIC)
+            .validated(cfb::validate)
+            .reading(cfb::HEAD_LEN, 0),
+        FileType::Cfb,
+    ),
+    (
+        Magic::starts_with(elf::MAGIC)
+            .validated(elf::validate)
+            .reading(elf::HEAD_LEN, 0),
+        FileType::Elf,
+    ),
+    (
+        Magic::starts_with(macho::MAGIC_32_BE).validated(macho::validate_thin),
+        FileType::MachO,
+    ),
+    (
+        Magic::starts_with(macho::MAGIC_32_LE).validated(macho::validate_thin),
+        FileType::MachO,
+    ),
+    (
+        Magic::starts_with(macho::MAGIC_64_BE).validated(macho::validate_thin),
+        FileType::MachO,
+    ),
+    (
+        Magic::starts_with(macho::MAGIC_64_LE).validated(macho::validate_thin),
+        FileType::MachO,
+    ),
+    (
+        Magic::starts_with(macho::FAT_MAGIC)
+            .validated(macho::validate_fat)
+            .reading(macho::HEAD_LEN, 0),
+        FileType::MachO,
+    ),
+    (
+        Magic::starts_with(macho::FAT_MAGIC_64)
+            .validated(macho::validate_fat)
+            .reading(macho::HEAD_LEN, 0),
+        FileType::MachO,
+    ),
+    (
+        Magic::starts_with(bytecode::WASM_MAGIC).validated(bytecode::validate_wasm),
+        FileType::Wasm,
+    ),
+    (
+        Magic::starts_with(bytecode::CLASS_MAGIC).validated(bytecode::validate_class),
+        FileType::JavaClass,
+    ),
+    (
+        Magic::starts_with(bytecode::DEX_MAGIC).validated(bytecode::validate_dex),
+        FileType::Dex,
+    ),
+    (
+        Magic::starts_with(bytecode::ODEX_MAGIC)
+            .validated(bytecode::validate_odex)
+            .reading(bytecode::HEAD_LEN, 0),
+        FileType::Dex,
+    ),
+    (
+        Magic::starts_with(bytecode::BITCODE_MAGIC),
+        FileType::LlvmBitcode,
+    ),
+    (
+        Magic::starts_with(bytecode::BITCODE_WRAPPER_MAGIC)
+            .validated(bytecode::validate_bitcode_wrapper)
+            .reading(bytecode::HEAD_LEN, 0),
+        FileType::LlvmBitcode,
+    ),
     (Magic::starts_with(b"BZh"), FileType::Bzip2),
-    (Magic::starts_with(&[0x50, 0x4b, 0x03, 0x04]), FileType::Zip),
+    (
+        Magic::starts_with(&[0x1f, 0x8b, 0x08]).validated(compress::validate_gzip),
+        FileType::Gzip,
+    ),
+    (
+        Magic::starts_with(&[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+        FileType::Xz,
+    ),
+    (Magic::starts_with(compress::ZSTD_MAGIC), FileType::Zstd),
+    (Magic::starts_with(compress::LZ4_MAGIC), FileType::Lz4),
+    (Magic::starts_with(&[0x02, 0x21, 0x4c, 0x18]), FileType::Lz4),
+    // Skippable frames can come before either format's first real frame.
+    (
+        Magic::starts_with_offset(1, &[0x2a, 0x4d, 0x18])
+            .validated(compress::validate_skippable_zstd)
+            .reading(compress::HEAD_LEN, 0),
+        FileType::Zstd,
+    ),
+    (
+        Magic::starts_with_offset(1, &[0x2a, 0x4d, 0x18])
+            .validated(compress::validate_skippable_lz4)
+            .reading(compress::HEAD_LEN, 0),
+        FileType::Lz4,
+    ),
+    (
+        Magic::starts_with(&[0x37, 0x7a, 0xbc, 0xaf, 0x27, 0x1c]),
+        FileType::SevenZip,
+    ),
+    (Magic::starts_with(rar::RAR5_MAGIC), FileType::Rar),
+    (Magic::starts_with(rar::RAR4_MAGIC), FileType::Rar),
+    (Magic::starts_with(rar::RAR14_MAGIC), FileType::Rar),
+    (
+        Magic::starts_with(&[0x50, 0x4b, 0x03, 0x04]).reading(zip::HEAD_LEN, zip::TAIL_LEN),
+        FileType::Zip,
+    ),
     (Magic::starts_with(&[0x50, 0x4b, 0x05, 0x06]), FileType::Zip),
     (Magic::starts_with_offset(0x1e, b"PKLITE"), FileType::Zip),
+    // Spanned archives start with a marker before the first local header; "PK00" is left
+    // when a spanned archive turned out to need only one segment.
+    (
+        Magic::starts_with(b"PK\x07\x08PK\x03\x04").reading(zip::HEAD_LEN, zip::TAIL_LEN),
+        FileType::Zip,
+    ),
+    (
+        Magic::starts_with(b"PK00PK\x03\x04").reading(zip::HEAD_LEN, zip::TAIL_LEN),
+        FileType::Zip,
+    ),
     (
-        Magic::starts_with_offset(0x101, b"ustar  \0"),
+        Magic::starts_with_offset(0x101, b"ustar  \0")
+            .validated(tar::validate_checksum)
+            .reading(tar::BLOCK_LEN, 0),
         FileType::Tar,
     ),
+    (
+        Magic::starts_with_offset(0x101, b"ustar\0")
+            .validated(tar::validate_checksum)
+            .reading(tar::BLOCK_LEN, 0),
+        FileType::Tar,
+    ),
+    // These have no magic at a fixed offset, so they go last.
+    (
+        Magic::probe(zip::validate_eocd).reading(zip::HEAD_LEN, zip::TAIL_LEN),
+        FileType::Zip,
+    ),
+    (
+        Magic::probe(tar::validate_v7).reading(tar::BLOCK_LEN, 0),
+        FileType::Tar,
+    ),
+    (
+        Magic::probe(tar::validate_empty).reading(tar::EMPTY_LEN, tar::EMPTY_LEN),
+        FileType::Tar,
+    ),
+    // Only the last two bytes of the magic are the same for every release, so the archives
+    // have their turn first.
+    (
+        Magic::starts_with_offset(2, bytecode::PYC_MAGIC_SUFFIX).validated(bytecode::validate_pyc),
+        FileType::Pyc,
+    ),
+    (
+        Magic::probe(document::validate_pdf_offset).reading(document::PDF_HEAD_LEN, 0),
+        FileType::Pdf,
+    ),
+    // Self-extracting archives are executables with a ZIP archive appended, and are more
+    // useful reported as the archive, so these come after the ZIP probe.
+    (
+        Magic::starts_with(pe::MZ_MAGIC)
+            .validated(pe::validate_pe)
+            .reading(pe::HEAD_LEN, 0),
+        FileType::Pe,
+    ),
+    (
+        Magic::starts_with(pe::MZ_MAGIC).validated(pe::validate_dos),
+        FileType::DosExe,
+    ),
 ];
 
-pub fn detect_filetype(bytes: &[u8]) -> Option<FileType> {
+/// Number of bytes at the start of an input that any rule looks at.
+const HEAD_LEN: usize = {
+    let mut len = 0;
+    let mut i = 0;
+    while i < MAGIC_MAP.len() {
+        if MAGIC_MAP[i].0.head_len() > len {
+            len = MAGIC_MAP[i].0.head_len();
+        }
+        i += 1;
+    }
+    len
+};
+
+/// Number of bytes at the end of an input that any rule looks at.
+const TAIL_LEN: usize = {
+    let mut len = 0;
+    let mut i = 0;
+    while i < MAGIC_MAP.len() {
+        if MAGIC_MAP[i].0.tail_len() > len {
+            len = MAGIC_MAP[i].0.tail_len();
+        }
+        i += 1;
+    }
+    len
+};
+
+fn detect_sample(sample: &Sample<'_>) -> Option<FileType> {
+    MAGIC_MAP
+        .iter()
+        .find_map(|(magic, ty)| magic.evaluate(sample, *ty))
+        .map(|m| m.file_type)
+}
+
+/// Finds every type whose signature `bytes` matches: [`Strength::Strong`] matches first,
+/// then the most confident.
+///
+/// Unlike [`detect_filetype`], which stops at the first signature that matches, this lets
+/// callers resolve ambiguous inputs themselves. Each type appears at most once, with its
+/// best signature by the same ordering; ties keep the order [`detect_filetype`] would try
+/// them in.
+pub fn detect_all(bytes: &[u8]) -> Vec<Match> {
+    let sample = Sample::new(bytes);
+    let mut matches: Vec<Match> = Vec::new();
+
     for (magic, ty) in MAGIC_MAP {
-        if bytes[magic.start.offset..].starts_with(magic.start.bytes)
-            && bytes[..bytes.len() - magic.end.offset].ends_with(magic.end.bytes)
-        {
-            return Some(*ty);
+        let new = match magic.evaluate(&sample, *ty) {
+            Some(new) => new,
+            None => continue,
+        };
+
+        // Compared after refinement, which can turn a ZIP rule's match into a DOCX one.
+        match matches.iter_mut().find(|m| m.file_type == new.file_type) {
+            Some(existing) if existing.rank() < new.rank() => *existing = new,
+            Some(_) => {}
+            None => matches.push(new),
         }
     }
 
-    None
+    matches.sort_by_key(|m| Reverse(m.rank()));
+    matches
+}
+
+/// Detects the type of the file whose contents are `bytes`.
+///
+/// Never panics: empty or truncated inputs that are too short for a signature simply don't
+/// match it, and `None` is returned if nothing matches at all.
+pub fn detect_filetype(bytes: &[u8]) -> Option<FileType> {
+    detect_sample(&Sample::new(bytes))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{detect_filetype, FileType};
+    use super::{
+        detect_all, detect_filetype, detect_from_reader, detect_from_seekable, Checks, FileType,
+        MachO, Match, Strength,
+    };
     use std::{
         fs,
-        io::{self, Read},
+        io::{self, Cursor, Read},
         path::Path,
     };
 
@@ -139,10 +1385,19 @@ mod tests {
 
     macro_rules! file_test {
         ($extension:ident, $variant:ident) => {
+            file_test!(
+                $extension,
+                concat!("test.", stringify!($extension)),
+                $variant
+            );
+        };
+        ($name:ident, $path:expr, $variant:ident) => {
             #[test]
-            fn $extension() -> io::Result<()> {
+            fn $name() -> io::Result<()> {
+                let bytes = get_bytes($path)?;
+                assert_eq!(detect_filetype(&bytes), Some(FileType::$variant));
                 assert_eq!(
-                    detect_filetype(&get_bytes(concat!("test.", stringify!($extension)))?),
+                    detect_from_seekable(Cursor::new(&bytes))?,
                     Some(FileType::$variant)
                 );
 
@@ -151,11 +1406,799 @@ mod tests {
         };
     }
 
+    const SAMPLES: &[(&str, FileType)] = &[
+        ("test.tga", FileType::Tga),
+        ("test.jpg", FileType::Jpeg),
+        ("test.png", FileType::Png),
+        ("test.bmp", FileType::Bmp),
+        ("test.gif", FileType::Gif),
+        ("test-87a.gif", FileType::Gif),
+        ("test.webp", FileType::WebP),
+        ("test.tif", FileType::Tiff),
+        ("test-be.tif", FileType::Tiff),
+        ("test.ico", FileType::Ico),
+        ("test.cur", FileType::Cur),
+        ("test.psd", FileType::Psd),
+        ("test.jp2", FileType::Jp2),
+        ("test.j2k", FileType::J2k),
+        ("test.jxl", FileType::JpegXl),
+        ("test-container.jxl", FileType::JpegXl),
+        ("test.qoi", FileType::Qoi),
+        ("test.heic", FileType::Heic),
+        ("test.heif", FileType::Heif),
+        ("test.avif", FileType::Avif),
+        ("test.m4a", FileType::M4a),
+        ("test.mp4", FileType::Mp4),
+        ("test.m4v", FileType::M4v),
+        ("test.mov", FileType::Mov),
+        ("test.3gp", FileType::ThreeGp),
+        ("test.3g2", FileType::ThreeG2),
+        ("test.mkv", FileType::Matroska),
+        ("test.webm", FileType::WebM),
+        ("test.avi", FileType::Avi),
+        ("test.flv", FileType::Flv),
+        ("test.mpg", FileType::MpegPs),
+        ("test.ts", FileType::MpegTs),
+        ("test.m2ts", FileType::MpegTs),
+        ("test.pdf", FileType::Pdf),
+        ("test.ps", FileType::PostScript),
+        ("test.eps", FileType::Eps),
+        ("test-dos.eps", FileType::Eps),
+        ("test.rtf", FileType::Rtf),
+        ("test.cfb", FileType::Cfb),
+        ("test.ttf", FileType::TrueType),
+        ("test.otf", FileType::OpenType),
+        ("test.ttc", FileType::FontCollection),
+        ("test.woff", FileType::Woff),
+        ("test.woff2", FileType::Woff2),
+        ("test.pfa", FileType::Type1),
+        ("test.pfb", FileType::Type1),
+        ("test.doc", FileType::Doc),
+        ("test.xls", FileType::Xls),
+        ("test.ppt", FileType::Ppt),
+        ("test.msg", FileType::Msg),
+        ("test.elf", FileType::Elf),
+        ("test-static.elf", FileType::Elf),
+        ("test.exe", FileType::Pe),
+        ("test.dll", FileType::Pe),
+        ("test-dos.exe", FileType::DosExe),
+        ("test.macho", FileType::MachO),
+        ("test-ppc.macho", FileType::MachO),
+        ("test-fat.macho", FileType::MachO),
+        ("test.wasm", FileType::Wasm),
+        ("test-component.wasm", FileType::Wasm),
+        ("test.class", FileType::JavaClass),
+        ("test.dex", FileType::Dex),
+        ("test.odex", FileType::Dex),
+        ("test.bc", FileType::LlvmBitcode),
+        ("test-wrapper.bc", FileType::LlvmBitcode),
+        ("test.pyc", FileType::Pyc),
+        ("test-hash.pyc", FileType::Pyc),
+        ("test.mp3", FileType::Mp3),
+        ("test-frames.mp3", FileType::Mp3),
+        ("test.aac", FileType::Aac),
+        ("test.flac", FileType::Flac),
+        ("test.ogg", FileType::Ogg),
+        ("test.wav", FileType::Wav),
+        ("test.aiff", FileType::Aiff),
+        ("test.mid", FileType::Midi),
+        ("test.zip", FileType::Zip),
+        ("test-spanned.zip", FileType::Zip),
+        ("test-sfx.zip", FileType::Zip),
+        ("test-zip64.zip", FileType::Zip),
+        ("test.docx", FileType::Docx),
+        ("test.xlsx", FileType::Xlsx),
+        ("test.pptx", FileType::Pptx),
+        ("test.odt", FileType::Odt),
+        ("test.ods", FileType::Ods),
+        ("test.odp", FileType::Odp),
+        ("test.odg", FileType::Odg),
+        ("test.epub", FileType::Epub),
+        ("test.jar", FileType::Jar),
+        ("test.apk", FileType::Apk),
+        ("test.bz2", FileType::Bzip2),
+        ("test.tar", FileType::Tar),
+        ("test-ustar.tar", FileType::Tar),
+        ("test-pax.tar", FileType::Tar),
+        ("test-v7.tar", FileType::Tar),
+        ("test-empty.tar", FileType::Tar),
+        ("test.gz", FileType::Gzip),
+        ("test.xz", FileType::Xz),
+        ("test.zst", FileType::Zstd),
+        ("test-skippable.zst", FileType::Zstd),
+        ("test.lz4", FileType::Lz4),
+        ("test.7z", FileType::SevenZip),
+        ("test.rar", FileType::Rar),
+        ("test-rar5.rar", FileType::Rar),
+    ];
+
+    #[test]
+    fn empty() {
+        assert_eq!(detect_filetype(&[]), None);
+    }
+
+    #[test]
+    fn truncated_prefixes() -> io::Result<()> {
+        for (path, ty) in SAMPLES {
+            let bytes = get_bytes(path)?;
+
+            for len in 0..bytes.len() {
+                // Prefixes may still match something (a JPEG keeps its SOI marker), the
+                // point is that none of them panic.
+                let detected = detect_filetype(&bytes[..len]);
+                if len < 2 {
+                    assert_eq!(detected, None, "{} truncated to {} bytes", path, len);
+                }
+            }
+
+            assert_eq!(detect_filetype(&bytes), Some(*ty), "{}", path);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn short_inputs() {
+        for len in 0..0x110 {
+            assert_eq!(detect_filetype(&vec![0; len]), None);
+        }
+    }
+
+    #[test]
+    fn all_matches() -> io::Result<()> {
+        for (path, ty) in SAMPLES {
+            let matches = detect_all(&get_bytes(path)?);
+            assert!(matches.iter().any(|m| m.file_type == *ty), "{}", path);
+
+            for (i, m) in matches.iter().enumerate() {
+                assert!(
+                    matches[..i]
+                        .iter()
+                        .all(|earlier| earlier.file_type != m.file_type),
+                    "{} has {:?} more than once",
+                    path,
+                    m.file_type
+                );
+            }
+        }
+
+        assert_eq!(detect_all(&[]), vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ambiguous_matches() {
+        // A ZIP local header padded out to look like a GNU tar header as well, but cut off
+        // before the tar checksum can be checked. The tar magic is longer, but only the ZIP
+        // signature is strong, so it comes first.
+        let mut bytes = vec![0; 0x200];
+        bytes[..4].copy_from_slice(b"PK\x03\x04");
+        bytes[0x101..0x109].copy_from_slice(b"ustar  \0");
+        let cut = &bytes[..0x110];
+
+        assert_eq!(detect_filetype(cut), Some(FileType::Zip));
+        assert_eq!(
+            detect_all(cut),
+            vec![
+                Match {
+                    file_type: FileType::Zip,
+                    confidence: 32,
+                    strength: Strength::Strong,
+                    checks: Checks {
+                        start: true,
+                        end: false,
+                        structure: false,
+                    },
+                },
+                Match {
+                    file_type: FileType::Tar,
+                    confidence: 64,
+                    strength: Strength::Weak,
+                    checks: Checks {
+                        start: true,
+                        end: false,
+                        structure: false,
+                    },
+                },
+            ]
+        );
+
+        // With the whole header there, its checksum doesn't add up, so it isn't tar at all.
+        assert!(detect_all(&bytes)
+            .iter()
+            .all(|m| m.file_type != FileType::Tar));
+    }
+
+    #[test]
+    fn tar_checksum() -> io::Result<()> {
+        for path in &["test.tar", "test-ustar.tar", "test-pax.tar"] {
+            let mut bytes = get_bytes(path)?;
+            // The last byte of the name, which the checksum covers.
+            bytes[99] ^= 0x20;
+            assert_eq!(detect_filetype(&bytes), None, "{}", path);
+        }
+
+        Ok(())
+    }
+
+    fn strength_of(bytes: &[u8], ty: FileType) -> Option<Strength> {
+        detect_all(bytes)
+            .into_iter()
+            .find(|m| m.file_type == ty)
+            .map(|m| m.strength)
+    }
+
+    #[test]
+    fn bmp_validation() -> io::Result<()> {
+        let bmp = get_bytes("test.bmp")?;
+        assert_eq!(strength_of(&bmp, FileType::Bmp), Some(Strength::Strong));
+
+        // Cutting the file short breaks the file size field.
+        assert_eq!(
+            strength_of(&bmp[..bmp.len() - 1], FileType::Bmp),
+            Some(Strength::Weak)
+        );
+        assert_eq!(
+            strength_of(b"BMW owners club newsletter", FileType::Bmp),
+            Some(Strength::Weak)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn jpeg_validation() -> io::Result<()> {
+        let jpg = get_bytes("test.jpg")?;
+        assert_eq!(strength_of(&jpg, FileType::Jpeg), Some(Strength::Strong));
+        assert_eq!(
+            strength_of(&jpg[..6], FileType::Jpeg),
+            Some(Strength::Strong)
+        );
+
+        assert_eq!(
+            strength_of(&[0xff, 0xd8, 0x00, 0x01, 0x02], FileType::Jpeg),
+            Some(Strength::Weak)
+        );
+        // A restart marker can't come straight after SOI.
+        assert_eq!(
+            strength_of(&[0xff, 0xd8, 0xff, 0xd0, 0x00, 0x04], FileType::Jpeg),
+            Some(Strength::Weak)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn icon_directory() -> io::Result<()> {
+        // Plenty of binary data starts with the four signature bytes.
+        assert_eq!(detect_filetype(&[0, 0, 1, 0, 0, 0, 0, 0]), None);
+
+        let mut ico = get_bytes("test.ico")?;
+        ico[6 + 12] = 0;
+        assert_eq!(detect_filetype(&ico), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn zip_eocd_must_end_input() -> io::Result<()> {
+        let mut sfx = get_bytes("test-sfx.zip")?;
+        sfx.push(0);
+        assert_eq!(detect_filetype(&sfx), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn zip_containers() -> io::Result<()> {
+        for (path, ty) in SAMPLES {
+            if ty.container() == Some(FileType::Zip) {
+                // Streams without an end only have the local headers to go on.
+                let bytes = get_bytes(path)?;
+                assert_eq!(
+                    detect_from_reader(Cursor::new(&bytes))?,
+                    Some(*ty),
+                    "{}",
+                    path
+                );
+            }
+        }
+
+        // Pushed past the head window, only the central directory is left to go on.
+        let mut bytes = vec![0; 4096];
+        bytes.extend(get_bytes("test.docx")?);
+        assert_eq!(detect_filetype(&bytes), Some(FileType::Docx));
+
+        assert_eq!(FileType::Zip.container(), None);
+        assert_eq!(FileType::Png.container(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skippable_frames() -> io::Result<()> {
+        let mut lz4 = b"\x5f\x2a\x4d\x18\x02\0\0\0hi".to_vec();
+        lz4.extend(get_bytes("test.lz4")?);
+        assert_eq!(detect_filetype(&lz4), Some(FileType::Lz4));
+
+        // Skippable frames with nothing after them could belong to either format.
+        assert_eq!(detect_filetype(b"\x50\x2a\x4d\x18\x02\0\0\0hi"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gzip_header_fields() -> io::Result<()> {
+        let mut gz = get_bytes("test.gz")?;
+        gz[3] = 0x80;
+        assert_eq!(detect_filetype(&gz), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn isobmff_brands() {
+        assert_eq!(
+            detect_filetype(b"\0\0\0\x14ftypmif1\0\0\0\0heic"),
+            Some(FileType::Heic)
+        );
+        assert_eq!(
+            detect_filetype(b"\0\0\0\x08moov\0\0\0\x08mvhd"),
+            Some(FileType::Mov)
+        );
+        // A well-formed box, but for a format we don't know.
+        assert_eq!(detect_filetype(b"\0\0\0\x14ftypcrx \0\0\0\x01crx "), None);
+    }
+
+    #[test]
+    fn ebml_doc_type() -> io::Result<()> {
+        let mut webm = get_bytes("test.webm")?;
+        webm[0x18] = b'W';
+        assert_eq!(detect_filetype(&webm), None);
+
+        // Cut off before the DocType, only the EBML magic is left to go on.
+        let mkv = get_bytes("test.mkv")?;
+        assert_eq!(detect_filetype(&mkv[..0x10]), Some(FileType::Matroska));
+        assert_eq!(
+            detect_all(&get_bytes("test.webm")?[..0x10])
+                .iter()
+                .map(|m| (m.file_type, m.strength))
+                .collect::<Vec<_>>(),
+            vec![(FileType::Matroska, Strength::Weak)]
+        );
+
+        // ffmpeg writes the header size, and may write element sizes, as eight-byte vints,
+        // whose first byte is nothing but the length marker.
+        let mut long_sizes = b"\x1a\x45\xdf\xa3".to_vec();
+        long_sizes.extend(&[0x01, 0, 0, 0, 0, 0, 0, 0x0f]);
+        long_sizes.extend(&[0x42, 0x82, 0x01, 0, 0, 0, 0, 0, 0, 0x04]);
+        long_sizes.extend(b"webm");
+        assert_eq!(detect_filetype(&long_sizes), Some(FileType::WebM));
+        assert_eq!(
+            detect_filetype(b"\x1a\x45\xdf\xa3\x01\0\0\0\0\0\0\0"),
+            Some(FileType::Matroska)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn transport_stream_sync() -> io::Result<()> {
+        let mut ts = get_bytes("test.ts")?;
+        ts[188 * 3] = 0;
+        assert_eq!(detect_filetype(&ts), None);
+
+        // A lone packet could be anything that starts with a "G".
+        let ts = get_bytes("test.ts")?;
+        assert_eq!(detect_filetype(&ts[..188]), None);
+        assert_eq!(detect_filetype(&ts[..188 * 2]), Some(FileType::MpegTs));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mpeg_frame_sync() -> io::Result<()> {
+        // A frame sync on its own, or at the start of unrelated data, isn't enough.
+        assert_eq!(detect_filetype(&[0xff, 0xfb, 0x90, 0x00]), None);
+        let mut bytes = vec![0xff, 0xfb, 0x90, 0x00];
+        bytes.resize(4096, 0x55);
+        assert_eq!(detect_filetype(&bytes), None);
+
+        // A frame whose header changes sample rate mid-stream breaks the run.
+        let mut frames = get_bytes("test-frames.mp3")?;
+        frames[417 * 2 + 2] = 0x94;
+        assert_eq!(detect_filetype(&frames), None);
+
+        // Nor does a short run of frames that stops partway through the input.
+        let frames = get_bytes("test-frames.mp3")?;
+        assert_eq!(detect_filetype(&frames[..417 * 2 + 10]), None);
+        assert_eq!(detect_filetype(&frames[..417 * 3]), Some(FileType::Mp3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn id3_tagged_audio() -> io::Result<()> {
+        let tag = b"ID3\x04\0\0\0\0\0\0";
+
+        let mut flac = tag.to_vec();
+        flac.extend(get_bytes("test.flac")?);
+        assert_eq!(detect_filetype(&flac), Some(FileType::Flac));
+
+        let mut aac = tag.to_vec();
+        aac.extend(get_bytes("test.aac")?);
+        assert_eq!(detect_filetype(&aac), Some(FileType::Aac));
+
+        // The size is seven bits per byte.
+        assert_eq!(detect_filetype(b"ID3\x03\0\0\0\0\0\x80"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pdf_header_offset() -> io::Result<()> {
+        let mut pdf = b"From: someone@example.com\r\n\r\n".to_vec();
+        pdf.extend(get_bytes("test.pdf")?);
+        assert_eq!(detect_filetype(&pdf), Some(FileType::Pdf));
+
+        // Readers stop looking after the first kilobyte.
+        let mut pdf = vec![b' '; 1024];
+        pdf.extend(get_bytes("test.pdf")?);
+        assert_eq!(detect_filetype(&pdf), None);
+
+        assert_eq!(detect_filetype(b"%PDF-x.y"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cfb_containers() -> io::Result<()> {
+        // Streams without the end still have the directory at the start to go on.
+        for (path, ty) in SAMPLES {
+            if ty.container() == Some(FileType::Cfb) {
+                let bytes = get_bytes(path)?;
+                assert_eq!(
+                    detect_from_reader(Cursor::new(&bytes))?,
+                    Some(*ty),
+                    "{}",
+                    path
+                );
+            }
+        }
+
+        // Cut off before its directory, a document is only known to be a compound file.
+        let doc = get_bytes("test.doc")?;
+        assert_eq!(detect_filetype(&doc[..1024]), Some(FileType::Cfb));
+
+        // Only a directory inside the first 4KiB is read, whether or not there's more input.
+        let moved = |sector: usize| -> io::Result<Vec<u8>> {
+            let mut doc = get_bytes("test.doc")?;
+            let directory = doc[1024..1536].to_vec();
+            doc.resize(512 * (sector + 2), 0);
+            doc[512 * (sector + 1)..].copy_from_slice(&directory);
+            doc[0x30..0x34].copy_from_slice(&(sector as u32).to_le_bytes());
+            Ok(doc)
+        };
+        assert_eq!(detect_filetype(&moved(6)?), Some(FileType::Doc));
+        assert_eq!(detect_filetype(&moved(7)?), Some(FileType::Cfb));
+
+        let mut doc = get_bytes("test.doc")?;
+        doc[0x1e] = 10;
+        assert_eq!(detect_filetype(&doc), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dos_stubs() -> io::Result<()> {
+        // An executable with an archive appended is reported as the archive.
+        let mut sfx = get_bytes("test.exe")?;
+        sfx.extend(get_bytes("test.zip")?);
+        assert_eq!(detect_filetype(&sfx), Some(FileType::Zip));
+        assert!(detect_all(&sfx).iter().any(|m| m.file_type == FileType::Pe));
+
+        // Without PE headers at `e_lfanew`, it's only a DOS program.
+        let mut exe = get_bytes("test.exe")?;
+        exe[0x80] = b'N';
+        assert_eq!(detect_filetype(&exe), Some(FileType::DosExe));
+
+        // "MZ" alone isn't enough.
+        assert_eq!(detect_filetype(b"MZ is a postcode in Manchester"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mach_o_forms() -> io::Result<()> {
+        // Each of the four thin magics, for either word size in either byte order, on the
+        // header of the 64-bit little-endian sample.
+        let thin = get_bytes("test.macho")?;
+        for magic in &[
+            [0xfe, 0xed, 0xfa, 0xce],
+            [0xce, 0xfa, 0xed, 0xfe],
+            [0xfe, 0xed, 0xfa, 0xcf],
+            [0xcf, 0xfa, 0xed, 0xfe],
+        ] {
+            let mut swapped = thin.clone();
+            swapped[..4].copy_from_slice(magic);
+            if magic[0] == 0xfe {
+                for field in swapped[4..16].chunks_exact_mut(4) {
+                    field.reverse();
+                }
+            }
+            assert_eq!(detect_filetype(&swapped), Some(FileType::MachO));
+        }
+
+        // A file type Mach-O doesn't define.
+        let mut unknown = thin;
+        unknown[12] = 0x40;
+        assert_eq!(detect_filetype(&unknown), None);
+
+        // A 64-bit slice table, with the same slices.
+        let fat = get_bytes("test-fat.macho")?;
+        let mut fat64 = vec![0xca, 0xfe, 0xba, 0xbf, 0, 0, 0, 2];
+        for entry in fat[8..48].chunks_exact(20) {
+            fat64.extend(&entry[..8]);
+            fat64.extend(&[0; 4]);
+            fat64.extend(&entry[8..12]);
+            fat64.extend(&[0; 4]);
+            fat64.extend(&entry[12..20]);
+            fat64.extend(&[0; 4]);
+        }
+        fat64.resize(0x80, 0);
+        fat64.extend(&fat[0x40..0x78]);
+        fat64.resize(0xc0, 0);
+        fat64.extend(&fat[0x80..]);
+        // Point the slices at their new offsets, 0x80 and 0xc0.
+        fat64[8 + 15] = 0x80;
+        fat64[40 + 15] = 0xc0;
+        assert_eq!(detect_filetype(&fat64), Some(FileType::MachO));
+        assert_eq!(MachO::parse(&fat64).map(|m| m.slices.len()), Some(2));
+
+        // Java class files share the universal magic, but have their version where the
+        // slice count would be.
+        let class = get_bytes("test.class")?;
+        assert_eq!(detect_filetype(&class), Some(FileType::JavaClass));
+        assert!(detect_all(&class)
+            .iter()
+            .all(|m| m.file_type != FileType::MachO));
+
+        // A slice that doesn't start with a thin header.
+        let mut corrupt = fat;
+        corrupt[0x80] = 0;
+        assert_eq!(detect_filetype(&corrupt), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pyc_magic() -> io::Result<()> {
+        // Short lines of CRLF text have the `\r\n`, but not a CPython magic number.
+        for text in &[&b"Hi\r\n"[..], b"ab\r\n", b"ok\r\nthanks\r\n"] {
+            assert_eq!(detect_filetype(text), None, "{:?}", text);
+        }
+
+        // A known magic cut off before its flags word is still worth a guess.
+        let pyc = get_bytes("test.pyc")?;
+        assert_eq!(detect_filetype(&pyc[..4]), Some(FileType::Pyc));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bytecode_headers() -> io::Result<()> {
+        // A core module version WebAssembly hasn't defined.
+        assert_eq!(detect_filetype(b"\0asm\x02\0\0\0"), None);
+
+        // Class file versions from before Java existed.
+        assert_eq!(detect_filetype(b"\xca\xfe\xba\xbe\0\0\0\x2c\0\x05"), None);
+
+        // A DEX header without the byte order tag.
+        let mut dex = get_bytes("test.dex")?;
+        dex[40] = 0;
+        assert_eq!(detect_filetype(&dex), None);
+
+        // Wrappers that don't point to what they wrap.
+        let mut odex = get_bytes("test.odex")?;
+        odex[40] = b'x';
+        assert_eq!(detect_filetype(&odex), None);
+        let mut wrapper = get_bytes("test-wrapper.bc")?;
+        wrapper[8] = 4;
+        assert_eq!(detect_filetype(&wrapper), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn font_table_directory() -> io::Result<()> {
+        let ttf = get_bytes("test.ttf")?;
+
+        // The TrueType signature alone is too common to go on.
+        let mut no_tables = ttf.clone();
+        no_tables[5] = 0;
+        assert_eq!(detect_filetype(&no_tables), None);
+
+        let mut bad_tag = ttf.clone();
+        bad_tag[12] = 0;
+        assert_eq!(detect_filetype(&bad_tag), None);
+
+        // Table data overlapping the directory, or running past the end of the input.
+        let mut overlapping = ttf.clone();
+        overlapping[12 + 11] = 0x10;
+        assert_eq!(detect_filetype(&overlapping), None);
+        assert_eq!(detect_filetype(&ttf[..ttf.len() - 1]), None);
+
+        // The same directory under Apple's signature.
+        let mut apple = ttf;
+        apple[..4].copy_from_slice(b"true");
+        assert_eq!(detect_filetype(&apple), Some(FileType::TrueType));
+
+        // A WOFF file whose header disagrees with the input about its length.
+        let mut woff = get_bytes("test.woff")?;
+        woff.push(0);
+        assert_eq!(detect_filetype(&woff), None);
+
+        // Type 1 fonts are PostScript, but it's the font that's reported.
+        let pfa = get_bytes("test.pfa")?;
+        assert_eq!(detect_filetype(&pfa), Some(FileType::Type1));
+        assert!(detect_all(&pfa)
+            .iter()
+            .any(|m| m.file_type == FileType::PostScript));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mime_round_trip() {
+        for ty in FileType::ALL {
+            assert_eq!(FileType::from_mime(ty.mime_type()), Some(*ty));
+        }
+    }
+
+    #[test]
+    fn extension_round_trip() {
+        for ty in FileType::ALL {
+            assert_eq!(ty.extension(), ty.extensions()[0]);
+
+            for ext in ty.extensions() {
+                // Shared extensions belong to the first type that uses them.
+                let owner = FileType::ALL
+                    .iter()
+                    .find(|other| other.extensions().contains(ext));
+                assert_eq!(FileType::from_extension(ext), owner.copied(), "{}", ext);
+                assert!(owner == Some(ty) || *ext == "exe", "{}", ext);
+            }
+        }
+    }
+
+    #[test]
+    fn extension_aliases() {
+        assert_eq!(FileType::from_extension("JPEG"), Some(FileType::Jpeg));
+        assert_eq!(FileType::from_extension(".jfif"), Some(FileType::Jpeg));
+        assert_eq!(FileType::from_extension("tbz2"), Some(FileType::Bzip2));
+        assert_eq!(FileType::from_extension("tgz"), Some(FileType::Gzip));
+        assert_eq!(FileType::from_extension("EXE"), Some(FileType::Pe));
+        assert_eq!(FileType::from_extension("txt"), None);
+        assert_eq!(FileType::from_extension(""), None);
+    }
+
+    #[test]
+    fn mime_aliases() {
+        assert_eq!(FileType::from_mime("image/jpg"), Some(FileType::Jpeg));
+        assert_eq!(
+            FileType::from_mime("application/x-zip-compressed"),
+            Some(FileType::Zip)
+        );
+        assert_eq!(
+            FileType::from_mime(" Image/PNG ; charset=binary"),
+            Some(FileType::Png)
+        );
+        assert_eq!(FileType::from_mime("text/plain"), None);
+        assert_eq!(FileType::from_mime(""), None);
+    }
+
     file_test!(tga, Tga);
     file_test!(jpg, Jpeg);
     file_test!(png, Png);
     file_test!(bmp, Bmp);
+    file_test!(gif, Gif);
+    file_test!(gif87a, "test-87a.gif", Gif);
+    file_test!(webp, WebP);
+    file_test!(tif, Tiff);
+    file_test!(tif_be, "test-be.tif", Tiff);
+    file_test!(ico, Ico);
+    file_test!(cur, Cur);
+    file_test!(psd, Psd);
+    file_test!(jp2, Jp2);
+    file_test!(j2k, J2k);
+    file_test!(jxl, JpegXl);
+    file_test!(jxl_container, "test-container.jxl", JpegXl);
+    file_test!(qoi, Qoi);
+    file_test!(heic, Heic);
+    file_test!(heif, Heif);
+    file_test!(avif, Avif);
+    file_test!(m4a, M4a);
+    file_test!(mp4, Mp4);
+    file_test!(m4v, M4v);
+    file_test!(mov, Mov);
+    file_test!(threegp, "test.3gp", ThreeGp);
+    file_test!(threeg2, "test.3g2", ThreeG2);
+    file_test!(mkv, Matroska);
+    file_test!(webm, WebM);
+    file_test!(avi, Avi);
+    file_test!(flv, Flv);
+    file_test!(mpg, MpegPs);
+    file_test!(ts, MpegTs);
+    file_test!(m2ts, MpegTs);
+    file_test!(pdf, Pdf);
+    file_test!(ps, PostScript);
+    file_test!(eps, Eps);
+    file_test!(eps_dos, "test-dos.eps", Eps);
+    file_test!(rtf, Rtf);
+    file_test!(cfb, Cfb);
+    file_test!(ttf, TrueType);
+    file_test!(otf, OpenType);
+    file_test!(ttc, FontCollection);
+    file_test!(woff, Woff);
+    file_test!(woff2, Woff2);
+    file_test!(pfa, Type1);
+    file_test!(pfb, Type1);
+    file_test!(doc, Doc);
+    file_test!(xls, Xls);
+    file_test!(ppt, Ppt);
+    file_test!(msg, Msg);
+    file_test!(elf, Elf);
+    file_test!(elf_static, "test-static.elf", Elf);
+    file_test!(exe, Pe);
+    file_test!(dll, Pe);
+    file_test!(exe_dos, "test-dos.exe", DosExe);
+    file_test!(macho, MachO);
+    file_test!(macho_ppc, "test-ppc.macho", MachO);
+    file_test!(macho_fat, "test-fat.macho", MachO);
+    file_test!(wasm, Wasm);
+    file_test!(wasm_component, "test-component.wasm", Wasm);
+    file_test!(class, JavaClass);
+    file_test!(dex, Dex);
+    file_test!(odex, Dex);
+    file_test!(bc, LlvmBitcode);
+    file_test!(bc_wrapper, "test-wrapper.bc", LlvmBitcode);
+    file_test!(pyc, Pyc);
+    file_test!(pyc_hash, "test-hash.pyc", Pyc);
+    file_test!(mp3, Mp3);
+    file_test!(mp3_frames, "test-frames.mp3", Mp3);
+    file_test!(aac, Aac);
+    file_test!(flac, Flac);
+    file_test!(ogg, Ogg);
+    file_test!(wav, Wav);
+    file_test!(aiff, Aiff);
+    file_test!(mid, Midi);
     file_test!(zip, Zip);
+    file_test!(zip_spanned, "test-spanned.zip", Zip);
+    file_test!(zip_sfx, "test-sfx.zip", Zip);
+    file_test!(zip64, "test-zip64.zip", Zip);
+    file_test!(docx, Docx);
+    file_test!(xlsx, Xlsx);
+    file_test!(pptx, Pptx);
+    file_test!(odt, Odt);
+    file_test!(ods, Ods);
+    file_test!(odp, Odp);
+    file_test!(odg, Odg);
+    file_test!(epub, Epub);
+    file_test!(jar, Jar);
+    file_test!(apk, Apk);
     file_test!(bz2, Bzip2);
     file_test!(tar, Tar);
+    file_test!(tar_ustar, "test-ustar.tar", Tar);
+    file_test!(tar_pax, "test-pax.tar", Tar);
+    file_test!(tar_v7, "test-v7.tar", Tar);
+    file_test!(tar_empty, "test-empty.tar", Tar);
+    file_test!(gz, Gzip);
+    file_test!(xz, Xz);
+    file_test!(zst, Zstd);
+    file_test!(zst_skippable, "test-skippable.zst", Zstd);
+    file_test!(lz4, Lz4);
+    file_test!(sevenzip, "test.7z", SevenZip);
+    file_test!(rar, Rar);
+    file_test!(rar5, "test-rar5.rar", Rar);
 }
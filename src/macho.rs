@@ -0,0 +1,234 @@
+//! Mach-O binaries: thin ones, in either byte order and word size, and universal ("fat")
+//! ones holding a slice for each architecture.
+
+use crate::{
+    bytes::{u32_be, u64_be},
+    Endianness, Sample, Strength,
+};
+use std::convert::TryFrom;
+
+pub(crate) const MAGIC_32_BE: &[u8] = &[0xfe, 0xed, 0xfa, 0xce];
+pub(crate) const MAGIC_32_LE: &[u8] = &[0xce, 0xfa, 0xed, 0xfe];
+pub(crate) const MAGIC_64_BE: &[u8] = &[0xfe, 0xed, 0xfa, 0xcf];
+pub(crate) const MAGIC_64_LE: &[u8] = &[0xcf, 0xfa, 0xed, 0xfe];
+/// Shared with Java class files, which [`fat_slices`] takes care to reject.
+pub(crate) const FAT_MAGIC: &[u8] = &[0xca, 0xfe, 0xba, 0xbe];
+pub(crate) const FAT_MAGIC_64: &[u8] = &[0xca, 0xfe, 0xba, 0xbf];
+
+const FAT_HEADER_LEN: usize = 8;
+const FAT_ARCH_LEN: usize = 20;
+const FAT_ARCH_64_LEN: usize = 32;
+/// Most slices a universal binary is taken to hold. A Java class file has its major
+/// version, 45 or more, where a universal binary has its slice count, so this must stay
+/// below that.
+const MAX_FAT_ARCHES: u32 = 20;
+/// Largest slice alignment, as a power of two.
+const MAX_FAT_ALIGN: u32 = 15;
+/// `MH_OBJECT` through `MH_FILESET`.
+const FILE_TYPES: std::ops::RangeInclusive<u32> = 1..=12;
+/// Capability bits in the CPU subtype, such as the pointer authentication ABI version.
+const CPU_SUBTYPE_MASK: u32 = 0xff00_0000;
+
+/// Bytes a validator needs to see the largest slice table.
+pub(crate) const HEAD_LEN: usize = FAT_HEADER_LEN + FAT_ARCH_64_LEN * MAX_FAT_ARCHES as usize;
+
+/// The architecture of a Mach-O slice, from its CPU type.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum MachOCpu {
+    X86,
+    X86_64,
+    Arm,
+    Arm64,
+    /// arm64 with 32-bit pointers, as watchOS uses.
+    Arm64_32,
+    PowerPc,
+    PowerPc64,
+    /// Any other architecture, by its CPU type.
+    Other(u32),
+}
+
+impl MachOCpu {
+    fn from_cpu_type(cpu_type: u32) -> Self {
+        match cpu_type {
+            7 => MachOCpu::X86,
+            0x0100_0007 => MachOCpu::X86_64,
+            12 => MachOCpu::Arm,
+            0x0100_000c => MachOCpu::Arm64,
+            0x0200_000c => MachOCpu::Arm64_32,
+            18 => MachOCpu::PowerPc,
+            0x0100_0012 => MachOCpu::PowerPc64,
+            other => MachOCpu::Other(other),
+        }
+    }
+}
+
+/// One architecture's code in a Mach-O binary.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct MachOSlice {
+    pub cpu: MachOCpu,
+    /// The CPU subtype, such as 2 for arm64e, without its capability bits.
+    pub cpu_subtype: u32,
+    /// Where the slice starts in the file, which is 0 for a thin binary.
+    pub offset: u64,
+}
+
+/// The architectures a Mach-O binary was built for.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct MachO {
+    /// Whether this is a universal binary rather than a thin one.
+    pub universal: bool,
+    /// A slice for each architecture, in the order the binary lists them. A thin binary
+    /// has exactly one.
+    pub slices: Vec<MachOSlice>,
+}
+
+impl MachO {
+    /// Parses the thin header or universal slice table `bytes` starts with, or returns
+    /// `None` if it isn't a Mach-O binary.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let (universal, slices) = match bytes.get(..4)? {
+            FAT_MAGIC => (true, fat_slices(bytes, FAT_ARCH_LEN)?),
+            FAT_MAGIC_64 => (true, fat_slices(bytes, FAT_ARCH_64_LEN)?),
+            _ => (false, vec![thin_slice(bytes)?]),
+        };
+
+        Some(MachO { universal, slices })
+    }
+}
+
+fn thin_endianness(bytes: &[u8]) -> Option<Endianness> {
+    match bytes.get(..4)? {
+        MAGIC_32_BE | MAGIC_64_BE => Some(Endianness::Big),
+        MAGIC_32_LE | MAGIC_64_LE => Some(Endianness::Little),
+        _ => None,
+    }
+}
+
+fn thin_slice(bytes: &[u8]) -> Option<MachOSlice> {
+    let endianness = thin_endianness(bytes)?;
+    let cpu_type = endianness.u32(bytes, 4)?;
+    let cpu_subtype = endianness.u32(bytes, 8)?;
+
+    if cpu_type == 0 || !FILE_TYPES.contains(&endianness.u32(bytes, 12)?) {
+        return None;
+    }
+
+    Some(MachOSlice {
+        cpu: MachOCpu::from_cpu_type(cpu_type),
+        cpu_subtype: cpu_subtype & !CPU_SUBTYPE_MASK,
+        offset: 0,
+    })
+}
+
+/// Reads a universal binary's slice table, requiring each slice to be aligned past it.
+fn fat_slices(bytes: &[u8], entry_len: usize) -> Option<Vec<MachOSlice>> {
+    let count = u32_be(bytes, 4)?;
+    if count == 0 || count > MAX_FAT_ARCHES {
+        return None;
+    }
+
+    let table_end = (FAT_HEADER_LEN + count as usize * entry_len) as u64;
+
+    (0..count as usize)
+        .map(|i| {
+            let entry = bytes.get(FAT_HEADER_LEN + i * entry_len..)?;
+            let cpu_type = u32_be(entry, 0)?;
+            let (offset, size, align) = if entry_len == FAT_ARCH_64_LEN {
+                (u64_be(entry, 8)?, u64_be(entry, 16)?, u32_be(entry, 24)?)
+            } else {
+                (
+                    u64::from(u32_be(entry, 8)?),
+                    u64::from(u32_be(entry, 12)?),
+                    u32_be(entry, 16)?,
+                )
+            };
+
+            let aligned = align <= MAX_FAT_ALIGN && offset % (1 << align) == 0;
+            if cpu_type == 0 || !aligned || offset < table_end || size == 0 {
+                return None;
+            }
+
+            let header = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| bytes.get(offset..));
+            if header.is_some_and(|header| header.len() >= 4 && thin_endianness(header).is_none()) {
+                return None;
+            }
+
+            Some(MachOSlice {
+                cpu: MachOCpu::from_cpu_type(cpu_type),
+                cpu_subtype: u32_be(entry, 4)? & !CPU_SUBTYPE_MASK,
+                offset,
+            })
+        })
+        .collect()
+}
+
+/// Checks the CPU type and that the file type is `MH_OBJECT` through `MH_FILESET`.
+pub(crate) fn validate_thin(sample: &Sample<'_>) -> Option<Strength> {
+    if sample.head.len() < 16 {
+        return Some(Strength::Weak);
+    }
+
+    thin_slice(sample.head).map(|_| Strength::Strong)
+}
+
+/// Accepts slice tables [`MachO::parse`] reads; a short one is left to the Java class rule.
+pub(crate) fn validate_fat(sample: &Sample<'_>) -> Option<Strength> {
+    MachO::parse(sample.head).map(|_| Strength::Strong)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MachO, MachOCpu, MachOSlice};
+    use std::{fs, io};
+
+    #[test]
+    fn parse() -> io::Result<()> {
+        assert_eq!(
+            MachO::parse(&fs::read("test.macho")?),
+            Some(MachO {
+                universal: false,
+                slices: vec![MachOSlice {
+                    cpu: MachOCpu::Arm64,
+                    cpu_subtype: 0,
+                    offset: 0,
+                }],
+            })
+        );
+        assert_eq!(
+            MachO::parse(&fs::read("test-ppc.macho")?),
+            Some(MachO {
+                universal: false,
+                slices: vec![MachOSlice {
+                    cpu: MachOCpu::PowerPc,
+                    cpu_subtype: 0,
+                    offset: 0,
+                }],
+            })
+        );
+        assert_eq!(
+            MachO::parse(&fs::read("test-fat.macho")?),
+            Some(MachO {
+                universal: true,
+                slices: vec![
+                    MachOSlice {
+                        cpu: MachOCpu::X86_64,
+                        cpu_subtype: 3,
+                        offset: 0x40,
+                    },
+                    MachOSlice {
+                        cpu: MachOCpu::Arm64,
+                        cpu_subtype: 2,
+                        offset: 0x80,
+                    },
+                ],
+            })
+        );
+
+        // A Java 8 class file: minor version 0, then major version 52.
+        assert_eq!(MachO::parse(b"\xca\xfe\xba\xbe\0\0\0\x34\0\x0a"), None);
+
+        Ok(())
+    }
+}
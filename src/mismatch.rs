@@ -0,0 +1,180 @@
+use crate::{detect_filetype, FileType};
+use std::path::Path;
+
+/// Whether a file's name agrees with its content, as returned by [`check_extension`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Verdict {
+    /// The content is the type the extension names, or a more specific type built on it
+    /// (say, a DOCX saved as `.zip`).
+    Consistent(FileType),
+    /// The content is a known type, but not the one the extension names (say, a ZIP saved
+    /// as `.png`).
+    Mismatched {
+        extension: FileType,
+        content: FileType,
+    },
+    /// The extension is known but the content isn't recognised.
+    UnknownContent { extension: FileType },
+    /// The content is recognised but the extension isn't, or there is none.
+    UnknownExtension { content: FileType },
+    /// Neither the extension nor the content is recognised.
+    Unknown,
+}
+
+/// The last extension of `name`, and the type it names, ignoring case.
+///
+/// For multi-part extensions like `.tar.bz2` only the last part is looked at, since that's
+/// the outermost format and the one the content is detected as. Dotfiles such as `.bz2`
+/// have no extension.
+fn extension_type(name: &Path) -> Option<(&str, FileType)> {
+    let name = name.file_name()?.to_str()?;
+    let (stem, extension) = name.rsplit_once('.')?;
+
+    if stem.is_empty() {
+        return None;
+    }
+
+    Some((extension, FileType::from_extension(extension)?))
+}
+
+/// Checks that the extension of `name` agrees with the detected type of `bytes`.
+///
+/// Only the file name part of `name` is used, so full upload paths can be passed as is. An
+/// extension several types share, such as `exe`, agrees with any of them.
+pub fn check_extension(name: impl AsRef<Path>, bytes: &[u8]) -> Verdict {
+    match (extension_type(name.as_ref()), detect_filetype(bytes)) {
+        (Some((extension, ty)), Some(content))
+            if ty == content
+                || content.container() == Some(ty)
+                || content
+                    .extensions()
+                    .iter()
+                    .any(|ext| ext.eq_ignore_ascii_case(extension)) =>
+        {
+            Verdict::Consistent(content)
+        }
+        (Some((_, extension)), Some(content)) => Verdict::Mismatched { extension, content },
+        (Some((_, extension)), None) => Verdict::UnknownContent { extension },
+        (None, Some(content)) => Verdict::UnknownExtension { content },
+        (None, None) => Verdict::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_extension, Verdict};
+    use crate::FileType;
+    use std::{fs, io};
+
+    #[test]
+    fn consistent() -> io::Result<()> {
+        let png = fs::read("test.png")?;
+        assert_eq!(
+            check_extension("test.png", &png),
+            Verdict::Consistent(FileType::Png)
+        );
+        assert_eq!(
+            check_extension("uploads/TEST.PNG", &png),
+            Verdict::Consistent(FileType::Png)
+        );
+
+        let jpg = fs::read("test.jpg")?;
+        assert_eq!(
+            check_extension("photo.jpeg", &jpg),
+            Verdict::Consistent(FileType::Jpeg)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn containers() -> io::Result<()> {
+        let docx = fs::read("test.docx")?;
+        assert_eq!(
+            check_extension("report.zip", &docx),
+            Verdict::Consistent(FileType::Docx)
+        );
+
+        // A plain archive isn't a document just because it's named like one.
+        assert_eq!(
+            check_extension("report.docx", &fs::read("test.zip")?),
+            Verdict::Mismatched {
+                extension: FileType::Docx,
+                content: FileType::Zip,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_extensions() -> io::Result<()> {
+        assert_eq!(
+            check_extension("setup.exe", &fs::read("test.exe")?),
+            Verdict::Consistent(FileType::Pe)
+        );
+        assert_eq!(
+            check_extension("GAME.EXE", &fs::read("test-dos.exe")?),
+            Verdict::Consistent(FileType::DosExe)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_part() -> io::Result<()> {
+        let bz2 = fs::read("test.bz2")?;
+        assert_eq!(
+            check_extension("backup.tar.bz2", &bz2),
+            Verdict::Consistent(FileType::Bzip2)
+        );
+        assert_eq!(
+            check_extension("backup.TAR.BZ2", &bz2),
+            Verdict::Consistent(FileType::Bzip2)
+        );
+        assert_eq!(
+            check_extension("backup.tbz2", &bz2),
+            Verdict::Consistent(FileType::Bzip2)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn mismatched() -> io::Result<()> {
+        assert_eq!(
+            check_extension("cat.png", &fs::read("test.zip")?),
+            Verdict::Mismatched {
+                extension: FileType::Png,
+                content: FileType::Zip,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown() -> io::Result<()> {
+        assert_eq!(
+            check_extension("notes.png", b"just some text"),
+            Verdict::UnknownContent {
+                extension: FileType::Png
+            }
+        );
+        assert_eq!(
+            check_extension("archive", &fs::read("test.zip")?),
+            Verdict::UnknownExtension {
+                content: FileType::Zip
+            }
+        );
+        assert_eq!(
+            check_extension(".zip", &fs::read("test.zip")?),
+            Verdict::UnknownExtension {
+                content: FileType::Zip
+            }
+        );
+        assert_eq!(check_extension("notes.txt", b"hello"), Verdict::Unknown);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+//! DOS and Windows executables: the `MZ` header every one of them starts with, and the PE
+//! headers it points to in Windows binaries.
+
+use crate::{
+    bytes::{u16_le, u32_le},
+    Sample, Strength,
+};
+
+pub(crate) const MZ_MAGIC: &[u8] = b"MZ";
+const PE_MAGIC: &[u8] = b"PE\0\0";
+
+/// Offset of `e_lfanew`, the pointer from the DOS header to the PE signature.
+const E_LFANEW: usize = 0x3c;
+/// Smallest DOS header, in 16-byte paragraphs.
+const MIN_DOS_HEADER_PARAGRAPHS: u16 = 2;
+const PAGE_LEN: u64 = 512;
+
+const COFF_HEADER_LEN: usize = 20;
+const IMAGE_FILE_DLL: u16 = 0x2000;
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+/// Offset of `Subsystem` in the optional header, the same for PE32 and PE32+.
+const SUBSYSTEM: usize = 68;
+/// Index of the CLR runtime header among the data directories.
+const CLR_DIRECTORY: u32 = 14;
+
+/// How far into an input the PE headers are looked for.
+pub(crate) const HEAD_LEN: usize = 1024;
+
+/// Whether a PE image uses 32 or 64-bit addresses, from its optional header's magic.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PeFormat {
+    Pe32,
+    /// PE32+, for 64-bit images.
+    Pe32Plus,
+}
+
+/// The architecture a PE image was built for, from the COFF header's `Machine`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PeMachine {
+    X86,
+    X86_64,
+    /// 32-bit ARM, including Thumb-2 images.
+    Arm,
+    Arm64,
+    Ia64,
+    RiscV64,
+    /// Any other architecture, by its `Machine` number.
+    Other(u16),
+}
+
+impl PeMachine {
+    fn from_machine(machine: u16) -> Self {
+        match machine {
+            0x14c => PeMachine::X86,
+            0x8664 => PeMachine::X86_64,
+            0x1c0 | 0x1c2 | 0x1c4 => PeMachine::Arm,
+            0xaa64 => PeMachine::Arm64,
+            0x200 => PeMachine::Ia64,
+            0x5064 => PeMachine::RiscV64,
+            other => PeMachine::Other(other),
+        }
+    }
+}
+
+/// The environment a PE image runs in, from its optional header's `Subsystem`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum PeSubsystem {
+    /// Kernel-mode drivers and native Windows processes.
+    Native,
+    WindowsGui,
+    WindowsConsole,
+    EfiApplication,
+    EfiBootServiceDriver,
+    EfiRuntimeDriver,
+    /// Any other subsystem, by its number.
+    Other(u16),
+}
+
+impl PeSubsystem {
+    fn from_subsystem(subsystem: u16) -> Self {
+        match subsystem {
+            1 => PeSubsystem::Native,
+            2 => PeSubsystem::WindowsGui,
+            3 => PeSubsystem::WindowsConsole,
+            10 => PeSubsystem::EfiApplication,
+            11 => PeSubsystem::EfiBootServiceDriver,
+            12 => PeSubsystem::EfiRuntimeDriver,
+            other => PeSubsystem::Other(other),
+        }
+    }
+}
+
+/// The details of a Windows PE image that say what it is and where it runs.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct Pe {
+    pub machine: PeMachine,
+    pub format: PeFormat,
+    /// Whether the image is a DLL rather than an executable.
+    pub dll: bool,
+    pub subsystem: PeSubsystem,
+    /// Whether a CLR runtime header makes this a .NET assembly.
+    pub clr: bool,
+}
+
+impl Pe {
+    /// Follows the DOS header at the start of `bytes` to the PE headers and parses them, or
+    /// returns `None` if `bytes` isn't a PE image.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(MZ_MAGIC) {
+            return None;
+        }
+
+        let signature = u32_le(bytes, E_LFANEW)? as usize;
+        if bytes.get(signature..signature.checked_add(PE_MAGIC.len())?)? != PE_MAGIC {
+            return None;
+        }
+
+        let coff = signature + PE_MAGIC.len();
+        let machine = PeMachine::from_machine(u16_le(bytes, coff)?);
+        let characteristics = u16_le(bytes, coff + 18)?;
+
+        let optional = coff + COFF_HEADER_LEN;
+        let (format, directories) = match u16_le(bytes, optional)? {
+            PE32_MAGIC => (PeFormat::Pe32, optional + 92),
+            PE32_PLUS_MAGIC => (PeFormat::Pe32Plus, optional + 108),
+            _ => return None,
+        };
+        let subsystem = PeSubsystem::from_subsystem(u16_le(bytes, optional + SUBSYSTEM)?);
+
+        // `directories` is the count, and the directories themselves follow it.
+        let clr = u32_le(bytes, directories).is_some_and(|count| count > CLR_DIRECTORY)
+            && u32_le(bytes, directories + 4 + CLR_DIRECTORY as usize * 8)
+                .is_some_and(|rva| rva != 0);
+
+        Some(Pe {
+            machine,
+            format,
+            dll: characteristics & IMAGE_FILE_DLL != 0,
+            subsystem,
+            clr,
+        })
+    }
+}
+
+/// Accepts images whose DOS header leads to a well-formed set of PE headers.
+pub(crate) fn validate_pe(sample: &Sample<'_>) -> Option<Strength> {
+    Pe::parse(sample.head).map(|_| Strength::Strong)
+}
+
+/// Checks the DOS header's page counts and header size add up, and fit in the input where
+/// its end was read.
+pub(crate) fn validate_dos(sample: &Sample<'_>) -> Option<Strength> {
+    let head = sample.head;
+    let (last_page, pages, header) = match (u16_le(head, 2), u16_le(head, 4), u16_le(head, 8)) {
+        (Some(last_page), Some(pages), Some(header)) => (last_page, pages, header),
+        _ => return Some(Strength::Weak),
+    };
+
+    if pages == 0 || u64::from(last_page) >= PAGE_LEN || header < MIN_DOS_HEADER_PARAGRAPHS {
+        return None;
+    }
+
+    // The last page is full when its byte count is zero.
+    let last_page_len = if last_page == 0 {
+        PAGE_LEN
+    } else {
+        u64::from(last_page)
+    };
+    let image_len = u64::from(pages - 1) * PAGE_LEN + last_page_len;
+
+    let past_end = sample.tail.is_some() && image_len > sample.len;
+    if u64::from(header) * 16 > image_len || past_end {
+        return None;
+    }
+
+    Some(Strength::Strong)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pe, PeFormat, PeMachine, PeSubsystem};
+    use std::{fs, io};
+
+    #[test]
+    fn parse() -> io::Result<()> {
+        assert_eq!(
+            Pe::parse(&fs::read("test.exe")?),
+            Some(Pe {
+                machine: PeMachine::X86_64,
+                format: PeFormat::Pe32Plus,
+                dll: false,
+                subsystem: PeSubsystem::WindowsConsole,
+                clr: false,
+            })
+        );
+        assert_eq!(
+            Pe::parse(&fs::read("test.dll")?),
+            Some(Pe {
+                machine: PeMachine::X86,
+                format: PeFormat::Pe32,
+                dll: true,
+                subsystem: PeSubsystem::WindowsGui,
+                clr: true,
+            })
+        );
+
+        // A DOS executable has no PE headers to point to.
+        assert_eq!(Pe::parse(&fs::read("test-dos.exe")?), None);
+
+        Ok(())
+    }
+}
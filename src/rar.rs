@@ -0,0 +1,52 @@
+//! RAR signatures, which changed with each generation of the format.
+
+pub(crate) const RAR14_MAGIC: &[u8] = b"RE~^";
+pub(crate) const RAR4_MAGIC: &[u8] = b"Rar!\x1a\x07\x00";
+pub(crate) const RAR5_MAGIC: &[u8] = b"Rar!\x1a\x07\x01\x00";
+
+/// Which generation of the RAR format an archive uses.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum RarVersion {
+    /// RAR 1.4 and earlier.
+    Rar14,
+    /// RAR 1.5 up to 4.x.
+    Rar4,
+    /// RAR 5.0 and later, which unrar versions before 5 can't extract.
+    Rar5,
+}
+
+impl RarVersion {
+    /// Works out which generation of RAR `bytes` is, or `None` if it isn't a RAR archive.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        [
+            (RAR5_MAGIC, RarVersion::Rar5),
+            (RAR4_MAGIC, RarVersion::Rar4),
+            (RAR14_MAGIC, RarVersion::Rar14),
+        ]
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map(|(_, version)| *version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RarVersion;
+    use std::{fs, io};
+
+    #[test]
+    fn versions() -> io::Result<()> {
+        assert_eq!(
+            RarVersion::parse(&fs::read("test.rar")?),
+            Some(RarVersion::Rar4)
+        );
+        assert_eq!(
+            RarVersion::parse(&fs::read("test-rar5.rar")?),
+            Some(RarVersion::Rar5)
+        );
+        assert_eq!(RarVersion::parse(b"RE~^\0\0"), Some(RarVersion::Rar14));
+        assert_eq!(RarVersion::parse(b"Rar!\x1a\x07\x02"), None);
+
+        Ok(())
+    }
+}
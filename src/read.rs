@@ -0,0 +1,300 @@
+use crate::{detect_filetype, detect_sample, FileType, Sample, HEAD_LEN, TAIL_LEN};
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// A path that [`detect_path`] won't read because there's no file contents to detect.
+///
+/// It's returned inside an [`io::Error`] of kind [`io::ErrorKind::InvalidInput`]; use
+/// [`SpecialFile::from_io_error`] to get it back out.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum SpecialFile {
+    Directory,
+    /// A named pipe. Reading it would block or consume data meant for someone else.
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+    /// A regular file with no contents.
+    Empty,
+    /// Anything else that isn't a regular file.
+    Other,
+}
+
+impl SpecialFile {
+    /// Gets the special file kind out of an error returned by [`detect_path`].
+    pub fn from_io_error(err: &io::Error) -> Option<Self> {
+        err.get_ref()?.downcast_ref().copied()
+    }
+
+    fn from_metadata(metadata: &fs::Metadata) -> Option<Self> {
+        let ty = metadata.file_type();
+
+        if ty.is_file() {
+            return if metadata.len() == 0 {
+                Some(SpecialFile::Empty)
+            } else {
+                None
+            };
+        }
+
+        if ty.is_dir() {
+            return Some(SpecialFile::Directory);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+
+            if ty.is_fifo() {
+                return Some(SpecialFile::Fifo);
+            }
+            if ty.is_socket() {
+                return Some(SpecialFile::Socket);
+            }
+            if ty.is_char_device() {
+                return Some(SpecialFile::CharDevice);
+            }
+            if ty.is_block_device() {
+                return Some(SpecialFile::BlockDevice);
+            }
+        }
+
+        Some(SpecialFile::Other)
+    }
+}
+
+impl fmt::Display for SpecialFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SpecialFile::Directory => "is a directory",
+            SpecialFile::Fifo => "is a named pipe",
+            SpecialFile::Socket => "is a socket",
+            SpecialFile::CharDevice => "is a character device",
+            SpecialFile::BlockDevice => "is a block device",
+            SpecialFile::Empty => "is an empty file",
+            SpecialFile::Other => "is not a regular file",
+        })
+    }
+}
+
+impl Error for SpecialFile {}
+
+/// Reads until `len` bytes have been read or the reader is exhausted.
+fn read_up_to(reader: impl Read, len: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    reader.take(len as u64).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// Detects the type of a stream from its first few bytes.
+///
+/// At most `HEAD_LEN` bytes are read. Since the end of the stream isn't reached, types that
+/// need a trailer (TGA, PNG) are only detected if the whole stream fits in that window; use
+/// [`detect_from_seekable`] for those.
+pub fn detect_from_reader<R: Read>(reader: R) -> io::Result<Option<FileType>> {
+    let head = read_up_to(reader, HEAD_LEN)?;
+
+    if head.len() < HEAD_LEN {
+        return Ok(detect_filetype(&head));
+    }
+
+    Ok(detect_sample(&Sample {
+        head: &head,
+        tail: None,
+        len: head.len() as u64,
+    }))
+}
+
+/// Detects the type of a seekable stream, reading only the windows at its start and end
+/// that the signatures cover.
+///
+/// Detection always considers the stream from offset 0, whatever its current position. The
+/// position afterwards is unspecified.
+pub fn detect_from_seekable<R: Read + Seek>(mut reader: R) -> io::Result<Option<FileType>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+
+    if len <= (HEAD_LEN + TAIL_LEN) as u64 {
+        let bytes = read_up_to(&mut reader, len as usize)?;
+        return Ok(detect_filetype(&bytes));
+    }
+
+    let head = read_up_to(&mut reader, HEAD_LEN)?;
+    reader.seek(SeekFrom::Start(len - TAIL_LEN as u64))?;
+    let tail = read_up_to(&mut reader, TAIL_LEN)?;
+
+    Ok(detect_sample(&Sample {
+        head: &head,
+        tail: Some(&tail),
+        len,
+    }))
+}
+
+/// Detects the type of the file at `path`, reading only the windows at its start and end
+/// that the signatures cover.
+///
+/// Symlinks are followed. Directories, FIFOs, devices and empty files aren't read at all,
+/// and give an error that [`SpecialFile::from_io_error`] can classify.
+pub fn detect_path(path: impl AsRef<Path>) -> io::Result<Option<FileType>> {
+    let path = path.as_ref();
+
+    if let Some(special) = SpecialFile::from_metadata(&fs::metadata(path)?) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, special));
+    }
+
+    detect_from_seekable(fs::File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        detect_from_reader, detect_from_seekable, detect_path, SpecialFile, HEAD_LEN, TAIL_LEN,
+    };
+    use crate::FileType;
+    use std::{
+        env, fs,
+        io::{self, Cursor, Read, Seek, SeekFrom},
+    };
+
+    /// Wraps a reader and counts how many bytes were taken from it.
+    struct Counting<R> {
+        inner: R,
+        read: usize,
+    }
+
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    impl<R: Seek> Seek for Counting<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    /// A TGA is only recognised by its footer, so pad one out well past both windows.
+    fn large_tga() -> Vec<u8> {
+        let mut bytes = vec![0; 1 << 20];
+        bytes.extend_from_slice(b"TRUEVISION-XFILE.\0");
+        bytes
+    }
+
+    #[test]
+    fn reader_reads_only_head() -> io::Result<()> {
+        let mut bytes = vec![0; 1 << 20];
+        bytes[..4].copy_from_slice(b"PK\x03\x04");
+        let mut reader = Counting {
+            inner: Cursor::new(bytes),
+            read: 0,
+        };
+
+        assert_eq!(detect_from_reader(&mut reader)?, Some(FileType::Zip));
+        assert_eq!(reader.read, HEAD_LEN);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reader_skips_trailers() -> io::Result<()> {
+        assert_eq!(detect_from_reader(Cursor::new(large_tga()))?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn seekable_reads_head_and_tail() -> io::Result<()> {
+        let mut reader = Counting {
+            inner: Cursor::new(large_tga()),
+            read: 0,
+        };
+
+        assert_eq!(detect_from_seekable(&mut reader)?, Some(FileType::Tga));
+        assert_eq!(reader.read, HEAD_LEN + TAIL_LEN);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reader_length_unknown() -> io::Result<()> {
+        // ADTS frames that exactly fill the head window, with more after them. Only a stream
+        // that really ends with the frames would be taken as AAC.
+        let mut bytes = Vec::new();
+        for len in &[HEAD_LEN / 3, HEAD_LEN / 3, HEAD_LEN - HEAD_LEN / 3 * 2] {
+            let mut frame = vec![
+                0xff,
+                0xf1,
+                0x50,
+                0x80,
+                (len >> 3) as u8,
+                (len << 5) as u8,
+                0,
+            ];
+            frame[3] |= (len >> 11) as u8;
+            frame.resize(*len, 0);
+            bytes.extend_from_slice(&frame);
+        }
+        bytes.extend_from_slice(&[0; 1024]);
+
+        assert_eq!(detect_from_reader(Cursor::new(&bytes))?, None);
+        assert_eq!(detect_from_seekable(Cursor::new(&bytes))?, None);
+        assert_eq!(
+            detect_from_seekable(Cursor::new(&bytes[..HEAD_LEN]))?,
+            Some(FileType::Aac)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn empty_streams() -> io::Result<()> {
+        assert_eq!(detect_from_reader(io::empty())?, None);
+        assert_eq!(detect_from_seekable(Cursor::new([]))?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn path() -> io::Result<()> {
+        assert_eq!(detect_path("test.png")?, Some(FileType::Png));
+
+        Ok(())
+    }
+
+    #[test]
+    fn path_errors() -> io::Result<()> {
+        let special = |path| detect_path(path).map_err(|e| SpecialFile::from_io_error(&e));
+
+        assert_eq!(special("src"), Err(Some(SpecialFile::Directory)));
+        assert_eq!(
+            detect_path("does-not-exist").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let empty = env::temp_dir().join(format!("detect-filetype-empty-{}", std::process::id()));
+        fs::File::create(&empty)?;
+        let result = special(empty.to_str().unwrap());
+        fs::remove_file(&empty)?;
+        assert_eq!(result, Err(Some(SpecialFile::Empty)));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_devices() {
+        let err = detect_path("/dev/null").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            SpecialFile::from_io_error(&err),
+            Some(SpecialFile::CharDevice)
+        );
+    }
+}
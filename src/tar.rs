@@ -0,0 +1,214 @@
+//! Tar header parsing, for the flavours that have magic and the ones that don't.
+
+use crate::{Sample, Strength};
+use std::ops::Range;
+
+/// Size of a tar header, and of every other block in the archive.
+pub(crate) const BLOCK_LEN: usize = 512;
+
+/// An archive ends with two zero-filled blocks, which is all an empty archive has.
+pub(crate) const EMPTY_LEN: usize = 2 * BLOCK_LEN;
+/// The record of 20 blocks tar pads archives to by default.
+const RECORD_LEN: usize = 20 * BLOCK_LEN;
+
+const NAME: Range<usize> = 0..100;
+const MODE: Range<usize> = 100..108;
+const CHECKSUM: Range<usize> = 148..156;
+const TYPEFLAG: usize = 156;
+const MAGIC: Range<usize> = 257..263;
+
+/// Which dialect of tar an archive was written in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum TarFlavour {
+    /// Unix V7, from before there was any magic in the header.
+    V7,
+    /// POSIX.1-1988 ustar, with `"ustar\0"` magic.
+    Ustar,
+    /// POSIX.1-2001 pax: ustar whose first entry is an extended header.
+    ///
+    /// A pax archive that didn't need any extended headers is plain ustar.
+    Pax,
+    /// GNU tar, with `"ustar  \0"` magic.
+    Gnu,
+    /// An archive with no entries, made up only of the end-of-archive blocks.
+    Empty,
+}
+
+impl TarFlavour {
+    /// Works out which flavour of tar `bytes` is, or `None` if it isn't a tar archive.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        flavour(&Sample::new(bytes))
+    }
+}
+
+fn flavour(sample: &Sample<'_>) -> Option<TarFlavour> {
+    let head = sample.head;
+
+    let magic_flavour = match head.get(MAGIC.start..MAGIC.end + 2) {
+        Some(b"ustar  \0") => Some(TarFlavour::Gnu),
+        Some(magic) if magic.starts_with(b"ustar\0") => Some(match head.get(TYPEFLAG) {
+            Some(b'x') | Some(b'g') => TarFlavour::Pax,
+            _ => TarFlavour::Ustar,
+        }),
+        _ => None,
+    };
+
+    if let Some(flavour) = magic_flavour {
+        // The checksum has the last word, if the input reaches the end of the header.
+        return (checksum_matches(head) != Some(false)).then_some(flavour);
+    }
+
+    if is_v7(head) {
+        Some(TarFlavour::V7)
+    } else if is_empty(sample) {
+        Some(TarFlavour::Empty)
+    } else {
+        None
+    }
+}
+
+/// Parses a numeric header field: octal digits, optionally padded with leading spaces and
+/// terminated by spaces or NULs.
+fn parse_octal(field: &[u8]) -> Option<u32> {
+    let start = field.iter().position(|&b| b != b' ')?;
+    let field = &field[start..];
+    let digits = field
+        .iter()
+        .position(|b| !(b'0'..=b'7').contains(b))
+        .unwrap_or(field.len());
+
+    if digits == 0 || field[digits..].iter().any(|&b| b != b' ' && b != 0) {
+        return None;
+    }
+
+    field[..digits].iter().try_fold(0u32, |acc, &b| {
+        acc.checked_mul(8)?.checked_add(u32::from(b - b'0'))
+    })
+}
+
+/// Whether the header's checksum field matches its contents, or `None` if the input ends
+/// before the header does.
+///
+/// The checksum is the sum of the header bytes with the field itself counted as spaces.
+/// Some old implementations summed signed bytes, so that's accepted too.
+fn checksum_matches(head: &[u8]) -> Option<bool> {
+    let header = head.get(..BLOCK_LEN)?;
+    let stored = match parse_octal(&header[CHECKSUM]) {
+        Some(stored) => i64::from(stored),
+        None => return Some(false),
+    };
+
+    let (unsigned, signed) = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if CHECKSUM.contains(&i) { b' ' } else { b })
+        .fold((0i64, 0i64), |(unsigned, signed), b| {
+            (unsigned + i64::from(b), signed + i64::from(b as i8))
+        });
+
+    Some(stored == unsigned || stored == signed)
+}
+
+/// A header without magic is only believed if it has a name, an octal mode and a valid
+/// checksum.
+fn is_v7(head: &[u8]) -> bool {
+    head.get(NAME).is_some_and(|name| name[0] != 0)
+        && head.get(MODE).and_then(parse_octal).is_some()
+        && checksum_matches(head) == Some(true)
+}
+
+/// An input is an empty archive if it's entirely zero and either just the end-of-archive
+/// blocks or those padded to a record. Other runs of zeros are more likely disk images or
+/// sparse files than archives.
+///
+/// Streams are only checked as far as they were read at each end.
+fn is_empty(sample: &Sample<'_>) -> bool {
+    let tail = match sample.tail {
+        Some(tail) => tail,
+        None => return false,
+    };
+
+    [EMPTY_LEN as u64, RECORD_LEN as u64].contains(&sample.len)
+        && sample.head.iter().all(|&b| b == 0)
+        && tail.iter().all(|&b| b == 0)
+}
+
+/// For headers with magic: a valid checksum confirms it, and a wrong one rules it out. A
+/// header cut short can't be checked, so is only weak.
+pub(crate) fn validate_checksum(sample: &Sample<'_>) -> Option<Strength> {
+    match checksum_matches(sample.head) {
+        Some(true) => Some(Strength::Strong),
+        Some(false) => None,
+        None => Some(Strength::Weak),
+    }
+}
+
+pub(crate) fn validate_v7(sample: &Sample<'_>) -> Option<Strength> {
+    if is_v7(sample.head) {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+/// An all-zero input is only weak evidence, since there's nothing tar-specific about it.
+pub(crate) fn validate_empty(sample: &Sample<'_>) -> Option<Strength> {
+    if is_empty(sample) {
+        Some(Strength::Weak)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_octal, TarFlavour};
+    use std::{fs, io};
+
+    #[test]
+    fn flavours() -> io::Result<()> {
+        for (path, flavour) in &[
+            ("test.tar", TarFlavour::Gnu),
+            ("test-ustar.tar", TarFlavour::Ustar),
+            ("test-pax.tar", TarFlavour::Pax),
+            ("test-v7.tar", TarFlavour::V7),
+            ("test-empty.tar", TarFlavour::Empty),
+        ] {
+            assert_eq!(
+                TarFlavour::parse(&fs::read(path)?),
+                Some(*flavour),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(TarFlavour::parse(&[0; 1024]), Some(TarFlavour::Empty));
+
+        Ok(())
+    }
+
+    #[test]
+    fn not_tar() -> io::Result<()> {
+        assert_eq!(TarFlavour::parse(&fs::read("test.png")?), None);
+        assert_eq!(TarFlavour::parse(&[0; 1000]), None);
+        assert_eq!(TarFlavour::parse(&[0; 1025]), None);
+        assert_eq!(TarFlavour::parse(&[0; 2048]), None);
+        assert_eq!(TarFlavour::parse(&[0; 20480]), None);
+
+        // V7 and GNU headers whose checksums were corrupted.
+        for path in &["test-v7.tar", "test.tar"] {
+            let mut bytes = fs::read(path)?;
+            bytes[0] ^= 1;
+            assert_eq!(TarFlavour::parse(&bytes), None, "{}", path);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn octal() {
+        assert_eq!(parse_octal(b"0000644\0"), Some(0o644));
+        assert_eq!(parse_octal(b" 10172\0 "), Some(0o10172));
+        assert_eq!(parse_octal(b"\0\0\0\0"), None);
+        assert_eq!(parse_octal(b"12a\0"), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! Validation for video containers: the EBML header Matroska and WebM share, and MPEG
+//! streams, whose magic is too short to trust on its own.
+
+use crate::{Sample, Strength};
+use std::convert::TryFrom;
+
+pub(crate) const EBML_MAGIC: &[u8] = &[0x1a, 0x45, 0xdf, 0xa3];
+const EBML_DOC_TYPE: u64 = 0x4282;
+/// How far into an input the EBML header is read.
+pub(crate) const EBML_HEAD_LEN: usize = 256;
+
+const TS_PACKET_LEN: usize = 188;
+/// M2TS puts a four-byte timestamp before each transport stream packet.
+const M2TS_PACKET_LEN: usize = 192;
+/// Packets whose sync bytes must line up before a transport stream is believed.
+const TS_PACKETS_CHECKED: usize = 4;
+pub(crate) const TS_HEAD_LEN: usize = M2TS_PACKET_LEN * TS_PACKETS_CHECKED;
+
+/// Reads an EBML variable-length integer, returning its value and length.
+///
+/// The length marker is kept for element IDs, which are written with it, and stripped for
+/// sizes.
+fn vint(bytes: &[u8], pos: usize, keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *bytes.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+
+    let marker = if keep_marker {
+        first
+    } else {
+        // An eight-byte vint's first byte is all marker, which `u8` can't shift right past.
+        first & 0xffu8.checked_shr(len as u32).unwrap_or(0)
+    };
+    let value = bytes
+        .get(pos + 1..pos + len)?
+        .iter()
+        .fold(u64::from(marker), |value, byte| {
+            value << 8 | u64::from(*byte)
+        });
+
+    Some((value, len))
+}
+
+/// Finds the DocType element in the EBML header at the start of `head`.
+///
+/// The outer `None` means the header is malformed; the inner one that it ended, or the
+/// input did, before a DocType turned up.
+fn doc_type(head: &[u8]) -> Option<Option<&[u8]>> {
+    let (size, len) = vint(head, EBML_MAGIC.len(), false)?;
+    let start = EBML_MAGIC.len() + len;
+    let end = usize::try_from(size)
+        .ok()
+        .and_then(|size| start.checked_add(size))
+        .map_or(head.len(), |end| end.min(head.len()));
+    let mut pos = start;
+
+    while pos < end {
+        let (id, id_len) = match vint(head, pos, true) {
+            Some(id) => id,
+            None => return Some(None),
+        };
+        let (size, size_len) = match vint(head, pos + id_len, false) {
+            Some(size) => size,
+            None => return Some(None),
+        };
+
+        let data = pos + id_len + size_len;
+        let next = usize::try_from(size)
+            .ok()
+            .and_then(|size| data.checked_add(size))?;
+
+        if id == EBML_DOC_TYPE {
+            let doc_type = head.get(data..next).map(|doc_type| {
+                let nul = doc_type.iter().position(|&byte| byte == 0);
+                &doc_type[..nul.unwrap_or(doc_type.len())]
+            });
+            return Some(doc_type);
+        }
+
+        pos = next;
+    }
+
+    Some(None)
+}
+
+/// Accepts an EBML header that declares `wanted` as its DocType.
+///
+/// Headers cut off before the DocType only count as Matroska, the more general of the two.
+fn validate_doc_type(sample: &Sample<'_>, wanted: &[u8]) -> Option<Strength> {
+    match doc_type(sample.head)? {
+        Some(doc_type) if doc_type == wanted => Some(Strength::Strong),
+        None if wanted == b"matroska" => Some(Strength::Weak),
+        _ => None,
+    }
+}
+
+pub(crate) fn validate_matroska(sample: &Sample<'_>) -> Option<Strength> {
+    validate_doc_type(sample, b"matroska")
+}
+
+pub(crate) fn validate_webm(sample: &Sample<'_>) -> Option<Strength> {
+    validate_doc_type(sample, b"webm")
+}
+
+/// Checks the pack header's marker bits, which differ between MPEG-1 and MPEG-2.
+pub(crate) fn validate_program_stream(sample: &Sample<'_>) -> Option<Strength> {
+    match sample.head.get(4) {
+        Some(byte) if byte & 0xc0 == 0x40 || byte & 0xf0 == 0x20 => Some(Strength::Strong),
+        Some(_) => None,
+        None => Some(Strength::Weak),
+    }
+}
+
+/// Checks that the sync byte of each packet lines up, `TS_PACKETS_CHECKED` of them or as
+/// many as fill a shorter input, read to the end, exactly.
+fn validate_packets(sample: &Sample<'_>, offset: usize, packet_len: usize) -> Option<Strength> {
+    let head = sample.head;
+    let mut packets = 0;
+
+    while packets < TS_PACKETS_CHECKED {
+        match head.get(offset + packets * packet_len) {
+            Some(0x47) => packets += 1,
+            Some(_) => return None,
+            None => break,
+        }
+    }
+
+    let filled = sample.tail.is_some() && sample.len == (packets * packet_len) as u64;
+    if packets == TS_PACKETS_CHECKED || (packets > 1 && filled) {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn validate_transport_stream(sample: &Sample<'_>) -> Option<Strength> {
+    validate_packets(sample, 0, TS_PACKET_LEN)
+}
+
+pub(crate) fn validate_m2ts(sample: &Sample<'_>) -> Option<Strength> {
+    validate_packets(sample, 4, M2TS_PACKET_LEN)
+}
@@ -0,0 +1,293 @@
+//! Finding ZIP archives by their end-of-central-directory (EOCD) record, which is where
+//! readers start and the only part whose position is fixed, relative to the end of the file,
+//! and telling apart the formats built on ZIP by the entries inside.
+
+use crate::{
+    bytes::{u16_le, u32_le, u64_le},
+    FileType, Sample, Strength,
+};
+use std::convert::TryFrom;
+
+const LOCAL_HEADER_SIG: &[u8] = b"PK\x03\x04";
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_SIG: &[u8] = b"PK\x01\x02";
+const EOCD_SIG: &[u8] = b"PK\x05\x06";
+const EOCD_LEN: usize = 22;
+const ZIP64_LOCATOR_SIG: &[u8] = b"PK\x06\x07";
+const ZIP64_LOCATOR_LEN: usize = 20;
+const ZIP64_EOCD_SIG: &[u8] = b"PK\x06\x06";
+const ZIP64_EOCD_LEN: usize = 56;
+/// Smallest possible central directory file header.
+const CENTRAL_HEADER_LEN: usize = 46;
+/// Local header flag for sizes that are only known from a data descriptor after the data.
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+
+/// Enough of the start of an archive to read the first few local headers, including an
+/// ODF or EPUB `mimetype` entry.
+pub(crate) const HEAD_LEN: usize = 1024;
+
+/// The EOCD record is followed only by the archive comment, which is at most 64KiB, and a
+/// ZIP64 archive has its locator and record right before it.
+pub(crate) const TAIL_LEN: usize =
+    ZIP64_EOCD_LEN + ZIP64_LOCATOR_LEN + EOCD_LEN + u16::MAX as usize;
+
+/// Finds the EOCD record in `tail`, searching back from the end for a signature whose
+/// comment length accounts exactly for the rest of the input.
+fn find_eocd(tail: &[u8]) -> Option<usize> {
+    let last = tail.len().checked_sub(EOCD_LEN)?;
+    let first = last.saturating_sub(u16::MAX as usize);
+
+    (first..=last).rev().find(|&pos| {
+        tail[pos..].starts_with(EOCD_SIG)
+            && u16_le(tail, pos + 20).map(usize::from) == Some(tail.len() - pos - EOCD_LEN)
+    })
+}
+
+/// Checks that the central directory described by the EOCD record at `eocd` in `tail` fits
+/// before it.
+///
+/// Offsets in self-extracting archives may or may not have been adjusted for the stub
+/// before the archive, so they are only required to fit before the EOCD, not to point at
+/// exactly the right place.
+fn eocd_consistent(sample: &Sample<'_>, tail: &[u8], eocd: usize) -> Option<bool> {
+    let window_start = sample.len - tail.len() as u64;
+
+    let disk_entries = u16_le(tail, eocd + 8)?;
+    let entries = u16_le(tail, eocd + 10)?;
+    let cd_size = u32_le(tail, eocd + 12)?;
+    let cd_offset = u32_le(tail, eocd + 16)?;
+
+    if entries == u16::MAX || cd_size == u32::MAX || cd_offset == u32::MAX {
+        return zip64_consistent(tail, window_start, eocd);
+    }
+
+    Some(
+        disk_entries <= entries
+            && u64::from(cd_offset) + u64::from(cd_size) <= window_start + eocd as u64
+            && u64::from(cd_size) >= u64::from(entries) * CENTRAL_HEADER_LEN as u64,
+    )
+}
+
+/// Finds the ZIP64 EOCD record belonging to the EOCD record at `eocd`, by way of the
+/// locator just before it.
+fn find_zip64_eocd(tail: &[u8], window_start: u64, eocd: usize) -> Option<usize> {
+    let locator = eocd.checked_sub(ZIP64_LOCATOR_LEN)?;
+    if !tail[locator..].starts_with(ZIP64_LOCATOR_SIG) {
+        return None;
+    }
+
+    // Either where the locator says, or (if that's off because of a stub before the archive)
+    // straight before the locator, which is where every writer puts it.
+    let pointed = u64_le(tail, locator + 8)?
+        .checked_sub(window_start)
+        .and_then(|pos| usize::try_from(pos).ok());
+    let adjacent = locator.checked_sub(ZIP64_EOCD_LEN);
+
+    pointed
+        .into_iter()
+        .chain(adjacent)
+        .find(|&pos| pos < locator && tail[pos..].starts_with(ZIP64_EOCD_SIG))
+}
+
+/// Checks the ZIP64 EOCD record that goes with the EOCD record at `eocd`.
+fn zip64_consistent(tail: &[u8], window_start: u64, eocd: usize) -> Option<bool> {
+    let record = match find_zip64_eocd(tail, window_start, eocd) {
+        Some(record) => record,
+        None => return Some(false),
+    };
+
+    let entries = u64_le(tail, record + 32)?;
+    let cd_size = u64_le(tail, record + 40)?;
+    let cd_offset = u64_le(tail, record + 48)?;
+
+    Some(
+        cd_offset
+            .checked_add(cd_size)
+            .is_some_and(|end| end <= window_start + record as u64)
+            && entries
+                .checked_mul(CENTRAL_HEADER_LEN as u64)
+                .is_some_and(|min| cd_size >= min),
+    )
+}
+
+/// Accepts inputs that end with a consistent EOCD record, wherever the archive starts.
+pub(crate) fn validate_eocd(sample: &Sample<'_>) -> Option<Strength> {
+    let tail = sample.tail?;
+    let eocd = find_eocd(tail)?;
+
+    if eocd_consistent(sample, tail, eocd)? {
+        Some(Strength::Strong)
+    } else {
+        None
+    }
+}
+
+/// Where the central directory lies in `tail`, if all of it is there.
+///
+/// It always ends right where the (ZIP64) EOCD record starts, so that and its size are
+/// used rather than its recorded offset, which isn't reliable in self-extracting archives.
+fn central_directory(sample: &Sample<'_>, tail: &[u8]) -> Option<(usize, usize)> {
+    let window_start = sample.len - tail.len() as u64;
+    let eocd = find_eocd(tail)?;
+    let cd_size = u32_le(tail, eocd + 12)?;
+
+    let (end, cd_size) = if cd_size == u32::MAX {
+        let record = find_zip64_eocd(tail, window_start, eocd)?;
+        (record, usize::try_from(u64_le(tail, record + 40)?).ok()?)
+    } else {
+        (eocd, usize::try_from(cd_size).ok()?)
+    };
+
+    Some((end.checked_sub(cd_size)?, end))
+}
+
+/// Names of the entries listed in the central directory.
+fn central_names<'a>(sample: &Sample<'_>, tail: &'a [u8]) -> Vec<&'a [u8]> {
+    let mut names = Vec::new();
+    let (mut pos, end) = match central_directory(sample, tail) {
+        Some(cd) => cd,
+        None => return names,
+    };
+
+    while pos + CENTRAL_HEADER_LEN <= end && tail[pos..].starts_with(CENTRAL_HEADER_SIG) {
+        let lens = (
+            u16_le(tail, pos + 28),
+            u16_le(tail, pos + 30),
+            u16_le(tail, pos + 32),
+        );
+        let (name_len, extra_len, comment_len) = match lens {
+            (Some(name), Some(extra), Some(comment)) => {
+                (usize::from(name), usize::from(extra), usize::from(comment))
+            }
+            _ => break,
+        };
+
+        let name_start = pos + CENTRAL_HEADER_LEN;
+        match tail.get(name_start..name_start + name_len) {
+            Some(name) => names.push(name),
+            None => break,
+        }
+
+        pos = name_start + name_len + extra_len + comment_len;
+    }
+
+    names
+}
+
+/// A local file header and the data following it.
+struct LocalEntry<'a> {
+    name: &'a [u8],
+    /// The entry's data, if it's stored uncompressed and entirely within the input.
+    stored: Option<&'a [u8]>,
+}
+
+/// The local headers at the start of the archive, for as long as they can be followed.
+fn local_entries(head: &[u8]) -> Vec<LocalEntry<'_>> {
+    let mut entries = Vec::new();
+
+    // Skip the marker at the start of a spanned archive.
+    let mut pos = if head.starts_with(b"PK\x07\x08") || head.starts_with(b"PK00") {
+        4
+    } else {
+        0
+    };
+
+    while head
+        .get(pos..)
+        .is_some_and(|rest| rest.starts_with(LOCAL_HEADER_SIG))
+    {
+        let fields = (
+            u16_le(head, pos + 6),
+            u16_le(head, pos + 8),
+            u32_le(head, pos + 18),
+            u16_le(head, pos + 26),
+            u16_le(head, pos + 28),
+        );
+        let (flags, method, size, name_len, extra_len) = match fields {
+            (Some(flags), Some(method), Some(size), Some(name), Some(extra)) => (
+                flags,
+                method,
+                size as usize,
+                usize::from(name),
+                usize::from(extra),
+            ),
+            _ => break,
+        };
+
+        let name_start = pos + LOCAL_HEADER_LEN;
+        let name = match head.get(name_start..name_start + name_len) {
+            Some(name) => name,
+            None => break,
+        };
+
+        let data_start = name_start + name_len + extra_len;
+        let stored = if method == 0 {
+            head.get(data_start..data_start.saturating_add(size))
+        } else {
+            None
+        };
+        entries.push(LocalEntry { name, stored });
+
+        // Without the size up front there's no telling where the next header starts.
+        if flags & FLAG_DATA_DESCRIPTOR != 0 {
+            break;
+        }
+        pos = data_start.saturating_add(size);
+    }
+
+    entries
+}
+
+/// Works out which ZIP-based format an archive is from its entries, or `None` if it looks
+/// like a plain ZIP archive.
+///
+/// ODF and EPUB declare themselves in an uncompressed `mimetype` entry that must come
+/// first. The others are recognised by entries that only they have: `[Content_Types].xml`
+/// plus the main part's directory for OOXML, `AndroidManifest.xml` for APKs (which are
+/// also JARs) and `META-INF/MANIFEST.MF` for JARs.
+pub(crate) fn subtype(sample: &Sample<'_>) -> Option<FileType> {
+    let local = local_entries(sample.head);
+
+    if let Some(LocalEntry {
+        name: b"mimetype",
+        stored: Some(mimetype),
+    }) = local.first()
+    {
+        let declared = std::str::from_utf8(mimetype)
+            .ok()
+            .and_then(FileType::from_mime)
+            .filter(|ty| ty.container() == Some(FileType::Zip));
+        if declared.is_some() {
+            return declared;
+        }
+    }
+
+    let mut names: Vec<&[u8]> = local.iter().map(|entry| entry.name).collect();
+    if let Some(tail) = sample.tail {
+        names.extend(central_names(sample, tail));
+    }
+
+    let has = |wanted: &[u8]| names.contains(&wanted);
+    let has_dir = |dir: &[u8]| names.iter().any(|name| name.starts_with(dir));
+
+    if has(b"[Content_Types].xml") {
+        if has_dir(b"word/") {
+            return Some(FileType::Docx);
+        }
+        if has_dir(b"xl/") {
+            return Some(FileType::Xlsx);
+        }
+        if has_dir(b"ppt/") {
+            return Some(FileType::Pptx);
+        }
+    }
+
+    if has(b"AndroidManifest.xml") {
+        return Some(FileType::Apk);
+    }
+    if has(b"META-INF/MANIFEST.MF") {
+        return Some(FileType::Jar);
+    }
+
+    None
+}